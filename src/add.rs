@@ -3,13 +3,14 @@ use crate::matrix::*;
 use clap::Parser;
 use rayon::prelude::*;
 use std::process::exit;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(version)]
 #[command(about = "A general image sum tool", long_about = None)]
 struct AddCli {
-    #[arg()]
-    mode: String,
+    #[arg(hide = true)]
+    command: String,
     #[arg()]
     input1: String,
     #[arg()]
@@ -18,44 +19,275 @@ struct AddCli {
     output: String,
     #[arg(short, long, default_value_t = false)]
     migrate: bool,
+    #[arg(long, default_value_t = String::from("rgba"))]
+    mode: String,
+}
+
+#[derive(Parser)]
+#[command(version)]
+#[command(about = "A general image blend tool", long_about = None)]
+struct BlendCli {
+    #[arg(hide = true)]
+    command: String,
+    #[arg()]
+    base: String,
+    #[arg()]
+    top: String,
+    #[arg()]
+    output: String,
+    #[arg(short, long, default_value_t = String::from("mix"))]
+    blend: String,
+    #[arg(short, long, default_value_t = 1.0)]
+    opacity: f32,
+    #[arg(long, default_value_t = String::from("rgba"))]
+    mode: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum Blend {
+    Mix,
+    Multiply,
+    Screen,
+    Overlay,
+    Difference,
+    Subtract,
+    Lighten,
+    Darken,
+    SoftLight,
+}
+
+impl FromStr for Blend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mix" => Ok(Blend::Mix),
+            "multiply" => Ok(Blend::Multiply),
+            "screen" => Ok(Blend::Screen),
+            "overlay" => Ok(Blend::Overlay),
+            "difference" => Ok(Blend::Difference),
+            "subtract" => Ok(Blend::Subtract),
+            "lighten" => Ok(Blend::Lighten),
+            "darken" => Ok(Blend::Darken),
+            "softlight" | "soft-light" | "soft_light" => Ok(Blend::SoftLight),
+            _ => Err(format!("Unknown blend mode '{}'", s)),
+        }
+    }
+}
+
+impl Blend {
+    /// Blend two channel values normalized to `[0, 1]`
+    #[inline]
+    pub fn apply(&self, base: f32, top: f32) -> f32 {
+        match self {
+            Blend::Mix => top,
+            Blend::Multiply => base * top,
+            Blend::Screen => 1.0 - (1.0 - base) * (1.0 - top),
+            Blend::Overlay => match base {
+                x if x <= 0.5 => 2.0 * base * top,
+                _ => 1.0 - 2.0 * (1.0 - base) * (1.0 - top),
+            },
+            Blend::Difference => (base - top).abs(),
+            Blend::Subtract => base - top,
+            Blend::Lighten => base.max(top),
+            Blend::Darken => base.min(top),
+            Blend::SoftLight => match top {
+                x if x <= 0.5 => base - (1.0 - 2.0 * top) * base * (1.0 - base),
+                _ => {
+                    let d = match base {
+                        x if x <= 0.25 => ((16.0 * base - 12.0) * base + 4.0) * base,
+                        _ => base.sqrt(),
+                    };
+                    base + (2.0 * top - 1.0) * (d - base)
+                }
+            },
+        }
+    }
 }
 
 pub fn add_cli() {
     let cli = AddCli::parse();
+    match cli.mode.as_str() {
+        "rgba" => add::<Rgba>(&cli),
+        "hsla" => add::<Hsla>(&cli),
+        "luva" => add::<Luva>(&cli),
+        _ => {
+            println!("Warning: unknown mode, fallback to RGBA");
+            add::<Rgba>(&cli);
+        }
+    }
+}
 
-    let a = Matrix::<Rgba>::_read_png(&cli.input1).unwrap_or_else(|e| {
+pub fn blend_cli() {
+    let cli = BlendCli::parse();
+    match cli.mode.as_str() {
+        "rgba" => blend::<Rgba>(&cli),
+        "hsla" => blend::<Hsla>(&cli),
+        "luva" => blend::<Luva>(&cli),
+        _ => {
+            println!("Warning: unknown mode, fallback to RGBA");
+            blend::<Rgba>(&cli);
+        }
+    }
+}
+
+fn read_pair<T>(input1: &str, input2: &str) -> (Matrix<T>, Matrix<T>)
+where
+    T: ColorValue + 'static,
+{
+    let a = Matrix::<T>::read_png(input1).unwrap_or_else(|e| {
         eprintln!("Read PNG 1 occurs error: {}", e);
         exit(1);
     });
-    let b = Matrix::<Rgba>::_read_png(&cli.input2).unwrap_or_else(|e| {
+    let b = Matrix::<T>::read_png(input2).unwrap_or_else(|e| {
         eprintln!("Read PNG 2 occurs error: {}", e);
         exit(1);
     });
-    let matrix = Matrix::<Rgba>::add(a, b, cli.migrate).unwrap_or_else(|e| {
+    (a, b)
+}
+
+fn add<T>(cli: &AddCli)
+where
+    T: ColorValue + 'static,
+{
+    let (a, b) = read_pair::<T>(&cli.input1, &cli.input2);
+    let scale = if cli.migrate { 2.0 } else { 1.0 };
+    let matrix = Matrix::<T>::add(a, b, |i, x, y| match T::CHANNEL_NAMES[i] {
+        // Hue is circular: move the second hue to the shortest arc from the first and wrap
+        'h' => wrap_hue((x + x + wrap_hue(y - x)) / scale),
+        _ => (x + y) / scale,
+    })
+    .unwrap_or_else(|e| {
         eprintln!("Add matrix occurs error: {}", e);
         exit(1);
     });
     matrix.write_png(&cli.output).unwrap_or_else(|e| eprintln!("Write PNG occurs error: {}", e));
 }
 
+fn blend<T>(cli: &BlendCli)
+where
+    T: ColorValue + 'static,
+{
+    let mode = Blend::from_str(&cli.blend).unwrap_or_else(|e| {
+        eprintln!("Invalid blend mode: {}", e);
+        exit(1);
+    });
+    let (a, b) = read_pair::<T>(&cli.base, &cli.top);
+    let matrix = Matrix::<T>::blend(a, b, mode, cli.opacity).unwrap_or_else(|e| {
+        eprintln!("Blend matrix occurs error: {}", e);
+        exit(1);
+    });
+    matrix.write_png(&cli.output).unwrap_or_else(|e| eprintln!("Write PNG occurs error: {}", e));
+}
+
 impl<T> Matrix<T>
 where
     T: ColorValue + 'static,
 {
-    pub fn add(a: Matrix<T>, b: Matrix<T>, migrate: bool) -> Result<Matrix<T>, String> {
+    /// Combine two matrices channel by channel, `op` receives the channel index and both values.
+    /// Results are clamped to the range of the color mode.
+    pub fn add<F>(a: Matrix<T>, b: Matrix<T>, op: F) -> Result<Matrix<T>, String>
+    where
+        F: Fn(usize, f32, f32) -> f32 + Sync,
+    {
         if a.rows != b.rows || a.cols != b.cols {
             return Err("The size of two matrix should be same".into());
         }
         let mut result = Matrix::<T>::new(a.rows, a.cols);
         result.data.par_iter_mut().enumerate().for_each(|(index, value)| {
             let mut r = [T::from(0u8); 4];
-            for i in 0..4 {
+            for (i, channel) in r.iter_mut().enumerate() {
                 let x: f32 = a.data[index][i].into();
                 let y: f32 = b.data[index][i].into();
-                r[i] = T::from(((x + y) / if migrate { 2.0 } else { 1.0 }).clamp(0.0, 255.0));
+                let (min, max) = T::bounds(i as u8);
+                *channel = T::from(op(i, x, y).clamp(min, max));
             }
             *value = r;
         });
         Ok(result)
     }
+
+    /// Composite `top` over `base` (Porter-Duff "over"), the top color is blended with `mode`
+    /// and weighted by its alpha times `opacity`. Channels are normalized to the range of the
+    /// color mode.
+    pub fn blend(
+        base: Matrix<T>,
+        top: Matrix<T>,
+        mode: Blend,
+        opacity: f32,
+    ) -> Result<Matrix<T>, String> {
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err("Opacity should be between 0 and 1".into());
+        }
+        if base.rows != top.rows || base.cols != top.cols {
+            return Err("The size of two matrix should be same".into());
+        }
+        let mut result = Matrix::<T>::new(base.rows, base.cols);
+        result.data.par_iter_mut().enumerate().for_each(|(index, value)| {
+            let (b, t) = (&base.data[index], &top.data[index]);
+            let ab = normalized(b, 3);
+            let at = normalized(t, 3) * opacity;
+            let alpha = at + ab * (1.0 - at);
+            let weight = if alpha > 0.0 { at / alpha } else { 0.0 };
+            for i in 0..3 {
+                let (x, y) = (b[i].into(), t[i].into());
+                // Blend modes have no meaning on a hue circle, hue mixes along the shorter arc
+                if T::CHANNEL_NAMES[i] == 'h' {
+                    value[i] = T::from(wrap_hue(x + wrap_hue(y - x) * weight));
+                    continue;
+                }
+                let (cb, ct) = (normalized(b, i), normalized(t, i));
+                let blended = (1.0 - ab) * ct + ab * mode.apply(cb, ct).clamp(0.0, 1.0);
+                let color = match alpha {
+                    a if a > 0.0 => (at * blended + ab * (1.0 - at) * cb) / a,
+                    _ => cb,
+                };
+                value[i] = denormalized::<T>(color, i);
+            }
+            value[3] = denormalized::<T>(alpha, 3);
+        });
+        Ok(result)
+    }
+}
+
+#[inline]
+fn normalized<T: ColorValue>(pixel: &[T; 4], channel: usize) -> f32 {
+    let (min, max) = T::bounds(channel as u8);
+    (pixel[channel].into() - min) / (max - min)
+}
+
+#[inline]
+fn denormalized<T: ColorValue>(x: f32, channel: usize) -> T {
+    let (min, max) = T::bounds(channel as u8);
+    T::quantize(min + x.clamp(0.0, 1.0) * (max - min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(rgba: [u8; 4]) -> Matrix<Rgba> {
+        Matrix { rows: 1, cols: 1, data: vec![rgba.map(Rgba)] }
+    }
+
+    #[test]
+    fn blend_composites_over() {
+        let background = [0, 0, 255, 255];
+        let over = |base, top, mode, opacity| {
+            Matrix::blend(pixel(base), pixel(top), mode, opacity).unwrap().data[0].map(|x| x.0)
+        };
+        assert_eq!(over(background, [255, 255, 255, 0], Blend::Mix, 1.0), background);
+        assert_eq!(over(background, [255, 0, 0, 255], Blend::Mix, 0.5), [128, 0, 128, 255]);
+        assert_eq!(over(background, [255, 0, 0, 128], Blend::Mix, 1.0), [128, 0, 127, 255]);
+        assert_eq!(over([0, 0, 0, 0], [255, 0, 0, 255], Blend::Multiply, 0.5), [255, 0, 0, 128]);
+        assert_eq!(over(background, [255, 255, 255, 255], Blend::Multiply, 1.0), background);
+    }
+
+    #[test]
+    fn hue_stays_on_the_circle() {
+        let hues =
+            |h| Matrix { rows: 1, cols: 1, data: vec![[Hsla(h), Hsla(1.0), Hsla(0.5), Hsla(1.0)]] };
+        let result = Matrix::blend(hues(-170.0), hues(170.0), Blend::Mix, 0.5).unwrap();
+        assert!((result.data[0][0].0.abs() - 180.0).abs() < 1e-3);
+    }
 }
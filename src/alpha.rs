@@ -0,0 +1,134 @@
+use crate::colormode::*;
+use crate::function::*;
+use crate::matrix::*;
+use rayon::prelude::*;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alpha {
+    /// Premultiply, filter every channel, then unpremultiply with the filtered alpha
+    Filter,
+    /// Same as `Filter`, but the source alpha is written back afterwards
+    Preserve,
+    /// Filter the straight color and make the result fully opaque
+    Ignore,
+}
+
+impl FromStr for Alpha {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "filter" => Ok(Alpha::Filter),
+            "preserve" => Ok(Alpha::Preserve),
+            "ignore" => Ok(Alpha::Ignore),
+            _ => Err(format!("Unknown alpha policy '{}'", s)),
+        }
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Prepare the matrix for `alpha`, let `filter` run `kernel` on it, then restore straight
+    /// colors. Under `Filter` and `Preserve` alpha is always filtered along with the colors, so
+    /// they are divided by the alpha they were actually blurred with. Channels the kernel does
+    /// not select take their source values back afterwards.
+    pub fn convolve_alpha<F>(&mut self, kernel: &Function<T>, alpha: Alpha, filter: F)
+    where
+        F: FnOnce(&mut Self, &Function<T>),
+    {
+        if alpha == Alpha::Ignore {
+            // Opaque straight colors are already premultiplied, kernels see the same input
+            let opaque = T::from(T::bounds(3).1);
+            self.data.par_iter_mut().for_each(|pixel| pixel[3] = opaque);
+            filter(self, kernel);
+            self.data.par_iter_mut().for_each(|pixel| pixel[3] = opaque);
+            return;
+        }
+
+        let mask = kernel.channels();
+        let keep = [!mask[0], !mask[1], !mask[2], !mask[3] || alpha == Alpha::Preserve];
+        let source = self.data.clone();
+        self.premultiply();
+        filter(self, &kernel.clone().masked([mask[0], mask[1], mask[2], true]));
+        self.unpremultiply(&source);
+        self.data.par_iter_mut().zip(source.par_iter()).for_each(|(pixel, source)| {
+            for i in (0..4).filter(|&i| keep[i]) {
+                pixel[i] = source[i];
+            }
+        });
+    }
+
+    pub fn premultiply(&mut self) {
+        let max = T::bounds(3).1;
+        self.data.par_iter_mut().for_each(|pixel| {
+            let a = pixel[3].into() / max;
+            for value in pixel.iter_mut().take(3) {
+                *value = T::quantize((*value).into() * a);
+            }
+        });
+    }
+
+    /// Undo `premultiply` with the filtered alpha. Where it dropped to zero the color is
+    /// undefined, so the straight `source` color is kept.
+    pub fn unpremultiply(&mut self, source: &[[T; 4]]) {
+        let max = T::bounds(3).1;
+        self.data.par_iter_mut().zip(source.par_iter()).for_each(|(pixel, source)| {
+            let a = pixel[3].into() / max;
+            for (i, value) in pixel.iter_mut().enumerate().take(3) {
+                *value = match a {
+                    a if a > 0.0 => T::quantize((*value).into() / a).clamp(i as u8),
+                    _ => source[i],
+                };
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite() -> Matrix<Rgbaf> {
+        let mut matrix = Matrix::new(9, 9);
+        for row in 3..6 {
+            for col in 3..6 {
+                matrix.set(row, col, [Rgbaf(255.0); 4]);
+            }
+        }
+        matrix
+    }
+
+    #[test]
+    fn premultiply_round_trip() {
+        let mut matrix = Matrix::<Rgbaf>::new(1, 4);
+        for (col, a) in [1.0, 3.0, 128.0, 255.0].into_iter().enumerate() {
+            matrix.set(0, col, [Rgbaf(200.0), Rgbaf(17.0), Rgbaf(93.0), Rgbaf(a)]);
+        }
+        let source = matrix.data.clone();
+        matrix.premultiply();
+        matrix.unpremultiply(&source);
+        for (pixel, source) in matrix.data.iter().zip(source.iter()) {
+            for i in 0..4 {
+                assert!((pixel[i].0 - source[i].0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn filtered_colors_have_no_fringe() {
+        let function = Function::from_str("3-gauss-blur-1").unwrap();
+        for mask in [[true; 4], [true, true, true, false]] {
+            let mut matrix = sprite();
+            matrix.convolve_alpha(&function.clone().masked(mask), Alpha::Filter, |m, k| {
+                m.convolve(k)
+            });
+            for pixel in matrix.data.iter().filter(|pixel| pixel[3].0 > 0.0) {
+                assert!(pixel[..3].iter().all(|x| (x.0 - 255.0).abs() < 1e-2));
+            }
+            assert_eq!(matrix.data[4 * 9 + 2][3].0 > 0.0, mask[3]);
+        }
+    }
+}
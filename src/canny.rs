@@ -0,0 +1,67 @@
+use crate::colormode::*;
+use crate::matrix::*;
+use rayon::prelude::*;
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Canny edge detector on the luminance, returns white edges on black. `low` and `high` are
+    /// hysteresis thresholds on the gradient magnitude of the `[0, 1]` luminance.
+    pub fn canny(&self, sigma: f32, low: f32, high: f32) -> Vec<[T; 4]> {
+        let (rows, cols) = (self.rows, self.cols);
+        let smooth = gauss_plane(&self.luminance(), rows, cols, sigma);
+        let (gx, gy) = sobel_plane(&smooth, rows, cols);
+        let magnitude: Vec<f32> =
+            gx.par_iter().zip(gy.par_iter()).map(|(x, y)| (x * x + y * y).sqrt()).collect();
+
+        // Non-maximum suppression along the gradient direction quantized to 45 degrees
+        let thin: Vec<f32> = (0..rows * cols)
+            .into_par_iter()
+            .map(|index| {
+                let (row, col) = (index / cols, index % cols);
+                if row == 0 || col == 0 || row == rows - 1 || col == cols - 1 {
+                    return 0.0;
+                }
+                let angle = gy[index].atan2(gx[index]).to_degrees().rem_euclid(180.0);
+                let (dr, dc): (isize, isize) = match angle {
+                    a if !(22.5..157.5).contains(&a) => (0, 1),
+                    a if a < 67.5 => (1, 1),
+                    a if a < 112.5 => (1, 0),
+                    _ => (1, -1),
+                };
+                let at = |r: isize, c: isize| magnitude[r as usize * cols + c as usize];
+                let (r, c) = (row as isize, col as isize);
+                let m = magnitude[index];
+                match m >= at(r + dr, c + dc) && m >= at(r - dr, c - dc) {
+                    true => m,
+                    false => 0.0,
+                }
+            })
+            .collect();
+
+        // Hysteresis, weak edges survive only when connected to a strong one
+        let mut edge = vec![false; rows * cols];
+        let mut stack: Vec<usize> = (0..rows * cols).filter(|&i| thin[i] >= high).collect();
+        stack.iter().for_each(|&i| edge[i] = true);
+        while let Some(index) = stack.pop() {
+            let (row, col) = ((index / cols) as isize, (index % cols) as isize);
+            for dr in -1..=1 {
+                for dc in -1..=1 {
+                    let (r, c) = (row + dr, col + dc);
+                    if r < 0 || c < 0 || r >= rows as isize || c >= cols as isize {
+                        continue;
+                    }
+                    let neighbor = r as usize * cols + c as usize;
+                    if !edge[neighbor] && thin[neighbor] >= low {
+                        edge[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        let (white, black) = (T::from_srgb([1.0; 4]), T::from_srgb([0.0, 0.0, 0.0, 1.0]));
+        edge.par_iter().map(|&e| if e { white } else { black }).collect()
+    }
+}
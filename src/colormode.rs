@@ -1,3 +1,4 @@
+use palette::IntoColor;
 use std::cmp::{PartialEq, PartialOrd};
 use std::fmt::*;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Rem, RemAssign, Sub, SubAssign};
@@ -18,17 +19,71 @@ pub trait ColorValue = Copy
     + From<u8>
     + From<f32>
     + Into<f32>
-    + Clamp;
+    + Clamp
+    + ChannelNames
+    + Linear
+    + FromSrgb
+    + Quantize;
 
 pub trait Clamp {
     fn clamp(&self, channel: u8) -> Self;
+    fn bounds(channel: u8) -> (f32, f32);
+}
+
+pub trait ChannelNames {
+    const CHANNEL_NAMES: [char; 4];
+}
+
+pub trait Linear: Sized {
+    /// Convert a channel value to linear light, keeping the scale of the channel
+    fn to_linear(channel: u8, value: f32) -> f32;
+    fn from_linear(channel: u8, value: f32) -> f32;
+    /// Relative luminance of a pixel in `[0, 1]`
+    fn luminance(pixel: &[Self; 4]) -> f32;
+}
+
+pub trait Quantize {
+    /// Nearest representable value, unlike `From<f32>` which truncates integer channels
+    fn quantize(x: f32) -> Self;
+}
+
+pub trait Widen: Sized {
+    /// Float color mode the convolver filters in, so intermediate results are not quantized
+    type Float: ColorValue + 'static;
+    fn widen(self) -> Self::Float;
+    fn narrow(value: Self::Float, channel: u8) -> Self;
+}
+
+pub trait FromSrgb: Sized {
+    /// Convert a straight sRGB color with components in `[0, 1]` into this color mode
+    fn from_srgb(color: [f32; 4]) -> [Self; 4];
+}
+
+#[inline]
+fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 { x / 12.92 } else { ((x + 0.055) / 1.055).powf(2.4) }
+}
+
+#[inline]
+fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 { x * 12.92 } else { 1.055 * x.max(0.0).powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Wrap a hue in degrees into `[-180, 180)`
+#[inline]
+pub fn wrap_hue(x: f32) -> f32 {
+    (x + 180.0).rem_euclid(360.0) - 180.0
 }
 
 macro_rules! impl_newtype {
-    ($type_name:ident, $inner_type:ty, $max0:expr, $max1:expr, $max2:expr, $min0:expr, $min1:expr, $min2:expr) => {
+    ($type_name:ident, $inner_type:ty, $names:expr, $max0:expr, $max1:expr, $max2:expr, $max3:expr, $min0:expr, $min1:expr, $min2:expr, $min3:expr) => {
         #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
         pub struct $type_name(pub $inner_type);
 
+        impl ChannelNames for $type_name {
+            const CHANNEL_NAMES: [char; 4] = $names;
+        }
+
         impl Clamp for $type_name {
             #[inline]
             fn clamp(&self, channel: u8) -> $type_name {
@@ -36,6 +91,18 @@ macro_rules! impl_newtype {
                     0 => $type_name(self.0.min($max0).max($min0)),
                     1 => $type_name(self.0.min($max1).max($min1)),
                     2 => $type_name(self.0.min($max2).max($min2)),
+                    3 => $type_name(self.0.min($max3).max($min3)),
+                    _ => unreachable!(),
+                }
+            }
+
+            #[inline]
+            fn bounds(channel: u8) -> (f32, f32) {
+                match channel {
+                    0 => ($min0 as f32, $max0 as f32),
+                    1 => ($min1 as f32, $max1 as f32),
+                    2 => ($min2 as f32, $max2 as f32),
+                    3 => ($min3 as f32, $max3 as f32),
                     _ => unreachable!(),
                 }
             }
@@ -158,6 +225,233 @@ macro_rules! impl_newtype {
     };
 }
 
-impl_newtype!(Rgba, u8, 255u8, 255u8, 255u8, 0u8, 0u8, 0u8);
-impl_newtype!(Hsla, f32, 180.0f32, 1.0f32, 1.0f32, -180.0f32, 0.0f32, 0.0f32);
-impl_newtype!(Luva, f32, 100.0f32, 176.0f32, 108.0f32, 0.0f32, -84.0f32, -135.0f32);
+impl_newtype!(Rgba, u8, ['r', 'g', 'b', 'a'], 255u8, 255u8, 255u8, 255u8, 0u8, 0u8, 0u8, 0u8);
+impl_newtype!(
+    Rgbaf,
+    f32,
+    ['r', 'g', 'b', 'a'],
+    255.0f32,
+    255.0f32,
+    255.0f32,
+    255.0f32,
+    0.0f32,
+    0.0f32,
+    0.0f32,
+    0.0f32
+);
+impl_newtype!(
+    Hsla,
+    f32,
+    ['h', 's', 'l', 'a'],
+    180.0f32,
+    1.0f32,
+    1.0f32,
+    1.0f32,
+    -180.0f32,
+    0.0f32,
+    0.0f32,
+    0.0f32
+);
+impl_newtype!(
+    Luva,
+    f32,
+    ['l', 'u', 'v', 'a'],
+    100.0f32,
+    176.0f32,
+    108.0f32,
+    1.0f32,
+    0.0f32,
+    -84.0f32,
+    -135.0f32,
+    0.0f32
+);
+
+impl Linear for Rgba {
+    #[inline]
+    fn to_linear(channel: u8, value: f32) -> f32 {
+        match channel {
+            3 => value,
+            _ => srgb_to_linear(value / 255.0) * 255.0,
+        }
+    }
+
+    #[inline]
+    fn from_linear(channel: u8, value: f32) -> f32 {
+        match channel {
+            3 => value,
+            _ => linear_to_srgb(value / 255.0) * 255.0,
+        }
+    }
+
+    #[inline]
+    fn luminance(pixel: &[Self; 4]) -> f32 {
+        0.2126 * srgb_to_linear(pixel[0].0 as f32 / 255.0)
+            + 0.7152 * srgb_to_linear(pixel[1].0 as f32 / 255.0)
+            + 0.0722 * srgb_to_linear(pixel[2].0 as f32 / 255.0)
+    }
+}
+
+impl Linear for Rgbaf {
+    #[inline]
+    fn to_linear(channel: u8, value: f32) -> f32 {
+        Rgba::to_linear(channel, value)
+    }
+
+    #[inline]
+    fn from_linear(channel: u8, value: f32) -> f32 {
+        Rgba::from_linear(channel, value)
+    }
+
+    #[inline]
+    fn luminance(pixel: &[Self; 4]) -> f32 {
+        0.2126 * srgb_to_linear(pixel[0].0 / 255.0)
+            + 0.7152 * srgb_to_linear(pixel[1].0 / 255.0)
+            + 0.0722 * srgb_to_linear(pixel[2].0 / 255.0)
+    }
+}
+
+/// HSL channels have no linear-light form, they are left as they are
+impl Linear for Hsla {
+    #[inline]
+    fn to_linear(_: u8, value: f32) -> f32 {
+        value
+    }
+
+    #[inline]
+    fn from_linear(_: u8, value: f32) -> f32 {
+        value
+    }
+
+    #[inline]
+    fn luminance(pixel: &[Self; 4]) -> f32 {
+        srgb_to_linear(pixel[2].0)
+    }
+}
+
+impl Linear for Luva {
+    #[inline]
+    fn to_linear(channel: u8, value: f32) -> f32 {
+        match channel {
+            0 if value > 8.0 => ((value + 16.0) / 116.0).powi(3) * 100.0,
+            0 => value / 903.3 * 100.0,
+            _ => value,
+        }
+    }
+
+    #[inline]
+    fn from_linear(channel: u8, value: f32) -> f32 {
+        match channel {
+            0 if value > 0.8856 => 116.0 * (value / 100.0).cbrt() - 16.0,
+            0 => value / 100.0 * 903.3,
+            _ => value,
+        }
+    }
+
+    #[inline]
+    fn luminance(pixel: &[Self; 4]) -> f32 {
+        Self::to_linear(0, pixel[0].0) / 100.0
+    }
+}
+
+impl FromSrgb for Rgba {
+    fn from_srgb(color: [f32; 4]) -> [Self; 4] {
+        color.map(|x| Rgba((x.clamp(0.0, 1.0) * 255.0).round() as u8))
+    }
+}
+
+impl FromSrgb for Rgbaf {
+    fn from_srgb(color: [f32; 4]) -> [Self; 4] {
+        color.map(|x| Rgbaf(x.clamp(0.0, 1.0) * 255.0))
+    }
+}
+
+impl FromSrgb for Hsla {
+    fn from_srgb(color: [f32; 4]) -> [Self; 4] {
+        let color: palette::Hsla =
+            palette::Srgba::new(color[0], color[1], color[2], color[3]).into_color();
+        [
+            Hsla(color.hue.into_degrees()),
+            Hsla(color.saturation),
+            Hsla(color.lightness),
+            Hsla(color.alpha),
+        ]
+    }
+}
+
+impl FromSrgb for Luva {
+    fn from_srgb(color: [f32; 4]) -> [Self; 4] {
+        let color: palette::Luva =
+            palette::Srgba::new(color[0], color[1], color[2], color[3]).into_color();
+        [Luva(color.l), Luva(color.u), Luva(color.v), Luva(color.alpha)]
+    }
+}
+
+impl Quantize for Rgba {
+    #[inline]
+    fn quantize(x: f32) -> Self {
+        Rgba(x.round() as u8)
+    }
+}
+
+impl Quantize for Rgbaf {
+    #[inline]
+    fn quantize(x: f32) -> Self {
+        Rgbaf(x)
+    }
+}
+
+impl Quantize for Hsla {
+    #[inline]
+    fn quantize(x: f32) -> Self {
+        Hsla(x)
+    }
+}
+
+impl Quantize for Luva {
+    #[inline]
+    fn quantize(x: f32) -> Self {
+        Luva(x)
+    }
+}
+
+impl Widen for Rgba {
+    type Float = Rgbaf;
+
+    #[inline]
+    fn widen(self) -> Rgbaf {
+        Rgbaf(self.0 as f32)
+    }
+
+    #[inline]
+    fn narrow(value: Rgbaf, channel: u8) -> Self {
+        Rgba::quantize(value.clamp(channel).0)
+    }
+}
+
+impl Widen for Hsla {
+    type Float = Hsla;
+
+    #[inline]
+    fn widen(self) -> Hsla {
+        self
+    }
+
+    #[inline]
+    fn narrow(value: Hsla, _: u8) -> Self {
+        value
+    }
+}
+
+impl Widen for Luva {
+    type Float = Luva;
+
+    #[inline]
+    fn widen(self) -> Luva {
+        self
+    }
+
+    #[inline]
+    fn narrow(value: Luva, _: u8) -> Self {
+        value
+    }
+}
@@ -1,7 +1,11 @@
+use crate::alpha::*;
 use crate::colormode::*;
+use crate::corners::*;
 use crate::function::*;
+use crate::mask::*;
 use crate::matrix::*;
 use crate::neighbors::*;
+use crate::region::*;
 use clap::Parser;
 use rayon::prelude::*;
 use std::process::exit;
@@ -22,34 +26,109 @@ struct ConvolveCli {
     iteration: usize,
     #[arg(short, long ,default_value_t = String::from("3-none"))]
     function: String,
+    #[arg(short, long)]
+    channels: Option<String>,
+    #[arg(short, long, default_value_t = String::from("filter"))]
+    alpha: String,
+    #[arg(short, long)]
+    mask: Option<String>,
+    #[arg(short, long, default_value_t = 1.0)]
+    strength: f32,
+    #[arg(short, long)]
+    roi: Vec<String>,
+    #[arg(long, default_value_t = 0.0)]
+    tolerance: f32,
     #[arg(long, default_value_t = '*')]
     indicator: char,
+    #[arg(long)]
+    points: Option<String>,
 }
 
 pub fn convolve_cli<T>()
 where
-    T: ColorValue + 'static,
+    T: ColorValue + Widen + 'static,
 {
     let cli = ConvolveCli::parse();
-    let mut matrix: Matrix<T> = Matrix::<T>::read_png(&cli.input).unwrap_or_else(|e| {
+    let input = Matrix::<T>::read_png(&cli.input).unwrap_or_else(|e| {
         eprintln!("Read PNG occurs error: {}", e);
         exit(1);
     });
+    let mut matrix = input.widen();
 
-    let function = Function::from_str(&cli.function).unwrap_or_else(|e| {
+    let mut function = Function::from_str(&cli.function).unwrap_or_else(|e| {
         eprintln!("Invalid function: {}", e);
         exit(1);
     });
+    if let Some(channels) = &cli.channels {
+        let mask = Function::<T::Float>::parse_channels(channels).unwrap_or_else(|e| {
+            eprintln!("Invalid channels: {}", e);
+            exit(1);
+        });
+        function = function.masked(mask);
+    }
+    let alpha = Alpha::from_str(&cli.alpha).unwrap_or_else(|e| {
+        eprintln!("Invalid alpha policy: {}", e);
+        exit(1);
+    });
+
+    if cli.strength.is_nan() || cli.strength < 0.0 {
+        eprintln!("Invalid strength: should not be negative");
+        exit(1);
+    }
+
+    let mask = cli.mask.as_ref().map(|path| {
+        Mask::read_png(path).unwrap_or_else(|e| {
+            eprintln!("Read mask PNG occurs error: {}", e);
+            exit(1);
+        })
+    });
+    let region = (!cli.roi.is_empty()).then(|| {
+        Region(
+            cli.roi
+                .iter()
+                .flat_map(|x| x.split(';'))
+                .map(|x| {
+                    Rect::from_str(x).unwrap_or_else(|e| {
+                        eprintln!("Invalid ROI: {}", e);
+                        exit(1);
+                    })
+                })
+                .collect(),
+        )
+    });
+    if let Some(path) = &cli.points {
+        let corners = Corners::from_str(&cli.function).unwrap_or_else(|e| {
+            eprintln!("Points need a corners function: {}", e);
+            exit(1);
+        });
+        matrix.write_points(&corners, path).unwrap_or_else(|e| {
+            eprintln!("Write points occurs error: {}", e);
+            exit(1);
+        });
+    }
+    let original = (mask.is_some() || cli.strength != 1.0).then(|| matrix.clone());
 
     let start = Instant::now();
-    for _ in 0..cli.iteration {
-        matrix.convolve(&function);
-        print!("{}", cli.indicator);
+    matrix.convolve_alpha(&function, alpha, |matrix, kernel| {
+        for _ in 0..cli.iteration {
+            let previous = (cli.tolerance > 0.0).then(|| matrix.clone());
+            matrix.convolve_region(kernel, region.as_ref());
+            print!("{}", cli.indicator);
+            if previous.is_some_and(|p| matrix.difference(&p) < cli.tolerance) {
+                break;
+            }
+        }
+    });
+    if let Some(original) = &original {
+        matrix.mix(original, mask.as_ref(), cli.strength).unwrap_or_else(|e| {
+            eprintln!("Mix matrix occurs error: {}", e);
+            exit(1);
+        });
     }
     let duration = start.elapsed();
     println!("\nTime elapsed: {:?}", duration);
 
-    matrix.write_png(&cli.output).unwrap_or_else(|e| {
+    Matrix::<T>::narrow(&matrix).write_png(&cli.output).unwrap_or_else(|e| {
         eprintln!("Write PNG occurs error: {}", e);
         exit(1);
     });
@@ -60,6 +139,33 @@ where
     T: ColorValue + 'static,
 {
     pub fn convolve(&mut self, kernel: &Function<T>) {
+        self.convolve_region(kernel, None);
+    }
+
+    /// Convolve only the pixels inside `region`, neighbors outside are still read. Whole-image
+    /// functions run on each rectangle cropped with a margin of the kernel size.
+    pub fn convolve_region(&mut self, kernel: &Function<T>, region: Option<&Region>) {
+        if kernel.is_global() {
+            let Some(region) = region else {
+                self.data = kernel.global(self).unwrap();
+                return;
+            };
+            let crops: Vec<_> = region
+                .0
+                .par_iter()
+                .filter_map(|rect| {
+                    let bounds = rect.expand(kernel.size(), self.rows, self.cols)?;
+                    let mut crop = self.crop(&bounds);
+                    crop.data = kernel.global(&crop).unwrap();
+                    Some((rect, bounds, crop))
+                })
+                .collect();
+            for (rect, bounds, crop) in crops {
+                self.paste(&crop, &bounds, rect);
+            }
+            return;
+        }
+
         let mut result = vec![[T::from(0u8); 4]; self.rows * self.cols];
         let size = kernel.size();
         let iter: isize = (size as isize - 1) / 2;
@@ -69,6 +175,10 @@ where
         result.par_iter_mut().enumerate().for_each(|(index, value)| {
             let row = (index / self.cols) as isize;
             let col = (index % self.cols) as isize;
+            if region.is_some_and(|r| !r.contains(row as usize, col as usize)) {
+                *value = self.data[index];
+                return;
+            }
 
             let mut neighbors = vec![[T::from(0u8); 4]; area];
             for drow in -iter..=iter {
@@ -80,7 +190,9 @@ where
                 }
             }
 
-            *value = kernel.calculate(Neighbors { data: neighbors, size: size });
+            let pos =
+                Position { row: row as usize, col: col as usize, rows: self.rows, cols: self.cols };
+            *value = kernel.calculate(Neighbors { data: neighbors, size: size, pos });
         });
 
         self.data = result;
@@ -0,0 +1,162 @@
+use crate::colormode::*;
+use crate::function::*;
+use crate::matrix::*;
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Measure {
+    Harris,
+    ShiTomasi,
+}
+
+/// Corner detector parsed from `size-corners-measure-output-σ-t`
+#[derive(Clone, Copy)]
+pub struct Corners {
+    pub size: usize,
+    pub measure: Measure,
+    pub overlay: bool,
+    pub sigma: f32,
+    pub threshold: f32,
+}
+
+impl FromStr for Corners {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = split_parts(s);
+        if parts.len() < 4 || parts[1] != "corners" {
+            return Err("Invalid corners function format".into());
+        }
+        let size = parts[0].parse::<usize>().map_err(|e| format!("Invalid size: {}", e))?;
+        let measure = match parts[2] {
+            "harris" => Measure::Harris,
+            "shi" => Measure::ShiTomasi,
+            _ => return Err("Unknown corner measure".into()),
+        };
+        let overlay = match parts[3] {
+            "map" => false,
+            "overlay" => true,
+            _ => return Err("Unknown corner output".into()),
+        };
+        let sigma = parts.get(4).and_then(|x| x.parse::<f32>().ok()).unwrap_or(1.5);
+        let threshold = parts.get(5).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.01);
+        if sigma <= 0.0 || !(0.0..=1.0).contains(&threshold) {
+            return Err("Sigma must be positive and threshold must be in [0, 1]".into());
+        }
+        Ok(Corners { size, measure, overlay, sigma, threshold })
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Gaussian-smoothed gradient products of the luminance, `(Ixx, Ixy, Iyy)`
+    pub fn structure_tensor(&self, sigma: f32) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
+        let (rows, cols) = (self.rows, self.cols);
+        let (gx, gy) = sobel_plane(&self.luminance(), rows, cols);
+        let product = |a: &[f32], b: &[f32]| {
+            let plane: Vec<f32> = a.par_iter().zip(b.par_iter()).map(|(x, y)| x * y).collect();
+            gauss_plane(&plane, rows, cols, sigma)
+        };
+        (product(&gx, &gx), product(&gx, &gy), product(&gy, &gy))
+    }
+
+    /// Harris response with `k = 0.04`, or the smaller eigenvalue of the structure tensor
+    pub fn corner_response(&self, measure: Measure, sigma: f32) -> Vec<f32> {
+        let (xx, xy, yy) = self.structure_tensor(sigma);
+        (0..self.rows * self.cols)
+            .into_par_iter()
+            .map(|i| {
+                let (trace, det) = (xx[i] + yy[i], xx[i] * yy[i] - xy[i] * xy[i]);
+                match measure {
+                    Measure::Harris => det - 0.04 * trace * trace,
+                    Measure::ShiTomasi => {
+                        trace / 2.0 - ((xx[i] - yy[i]).powi(2) / 4.0 + xy[i] * xy[i]).sqrt()
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Local maxima of the response within the window that reach `threshold` times the
+    /// strongest response, as `(x, y, response)`
+    pub fn corner_points(&self, corners: &Corners) -> Vec<(usize, usize, f32)> {
+        let (rows, cols) = (self.rows, self.cols);
+        let response = self.corner_response(corners.measure, corners.sigma);
+        let peak = response.par_iter().cloned().reduce(|| 0.0, f32::max);
+        let radius = (corners.size / 2).max(1) as isize;
+        (0..rows * cols)
+            .into_par_iter()
+            .filter_map(|i| {
+                let value = response[i];
+                if value <= 0.0 || value < corners.threshold * peak {
+                    return None;
+                }
+                let (row, col) = ((i / cols) as isize, (i % cols) as isize);
+                for r in (row - radius).max(0)..=(row + radius).min(rows as isize - 1) {
+                    for c in (col - radius).max(0)..=(col + radius).min(cols as isize - 1) {
+                        let other = response[r as usize * cols + c as usize];
+                        // Ties go to the first pixel in scan order
+                        if other > value || (other == value && (r, c) < (row, col)) {
+                            return None;
+                        }
+                    }
+                }
+                Some((col as usize, row as usize, value))
+            })
+            .collect()
+    }
+
+    /// Response map scaled by its maximum, or the image with corners marked by red crosses
+    pub fn corners(&self, corners: &Corners) -> Vec<[T; 4]> {
+        if !corners.overlay {
+            let response = self.corner_response(corners.measure, corners.sigma);
+            let peak = response.par_iter().cloned().reduce(|| 0.0, f32::max).max(f32::EPSILON);
+            return response
+                .par_iter()
+                .map(|x| {
+                    let x = (x / peak).clamp(0.0, 1.0);
+                    T::from_srgb([x, x, x, 1.0])
+                })
+                .collect();
+        }
+        let mut result = self.data.clone();
+        let red = T::from_srgb([1.0, 0.0, 0.0, 1.0]);
+        for (x, y, _) in self.corner_points(corners) {
+            for d in -2isize..=2 {
+                for (r, c) in [(y as isize + d, x as isize), (y as isize, x as isize + d)] {
+                    if r >= 0 && c >= 0 && r < self.rows as isize && c < self.cols as isize {
+                        result[r as usize * self.cols + c as usize] = red;
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Write detected corners as `x,y,response` lines
+    pub fn write_points(&self, corners: &Corners, filename: &str) -> io::Result<()> {
+        let mut points = self.corner_points(corners);
+        points.sort_by_key(|&(x, y, _)| (y, x));
+        let lines: String =
+            points.iter().map(|(x, y, response)| format!("{},{},{}\n", x, y, response)).collect();
+        fs::write(filename, format!("x,y,response\n{}", lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_parse_like_other_functions() {
+        let corners = Corners::from_str(" 5-corners - shi-overlay -2 - 0.05 ").unwrap();
+        assert!(corners.measure == Measure::ShiTomasi && corners.overlay);
+        assert_eq!((corners.size, corners.sigma, corners.threshold), (5, 2.0, 0.05));
+        assert!(Corners::from_str("5-corners-harris-map--1").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::colormode::*;
+use crate::expr::*;
+use crate::function::*;
+use crate::matrix::*;
+use clap::Parser;
+use rayon::prelude::*;
+use std::process::exit;
+
+#[derive(Parser)]
+#[command(version)]
+#[command(about = "A per-pixel image expression evaluator", long_about = None)]
+struct EvalCli {
+    #[arg(hide = true)]
+    command: String,
+    #[arg()]
+    output: String,
+    #[arg()]
+    expression: String,
+    /// Inputs as `name=path`, unnamed inputs are called a, b, c, ...
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    #[arg(short, long, default_value_t = String::from("012"))]
+    channels: String,
+    #[arg(long, default_value_t = String::from("rgba"))]
+    mode: String,
+}
+
+pub fn eval_cli() {
+    let cli = EvalCli::parse();
+    match cli.mode.as_str() {
+        "rgba" => eval::<Rgba>(&cli),
+        "hsla" => eval::<Hsla>(&cli),
+        "luva" => eval::<Luva>(&cli),
+        _ => {
+            println!("Warning: unknown mode, fallback to RGBA");
+            eval::<Rgba>(&cli);
+        }
+    }
+}
+
+fn eval<T>(cli: &EvalCli)
+where
+    T: ColorValue + 'static,
+{
+    let mut names = Vec::with_capacity(cli.inputs.len());
+    let mut inputs = Vec::with_capacity(cli.inputs.len());
+    let mut unnamed =
+        ('a'..='z').map(|c| c.to_string()).filter(|c| !Expr::RESERVED.contains(&c.as_str()));
+    for input in cli.inputs.iter() {
+        let (name, path) = match input.split_once('=') {
+            Some((name, path)) => (name.trim().to_string(), path),
+            None => (unnamed.next().unwrap_or_default(), input.as_str()),
+        };
+        let identifier = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !identifier || Expr::RESERVED.contains(&name.as_str()) || names.contains(&name) {
+            eprintln!("Invalid input name: {}", name);
+            exit(1);
+        }
+        let matrix = Matrix::<T>::read_png(path).unwrap_or_else(|e| {
+            eprintln!("Read PNG {} occurs error: {}", path, e);
+            exit(1);
+        });
+        names.push(name);
+        inputs.push(matrix);
+    }
+
+    let expr = Expr::parse(&cli.expression, &names).unwrap_or_else(|e| {
+        eprintln!("Invalid expression: {}", e);
+        exit(1);
+    });
+    let mask = Function::<T>::parse_channels(&cli.channels).unwrap_or_else(|e| {
+        eprintln!("Invalid channels: {}", e);
+        exit(1);
+    });
+    let matrix = Matrix::<T>::eval(&inputs, &expr, mask).unwrap_or_else(|e| {
+        eprintln!("Eval matrix occurs error: {}", e);
+        exit(1);
+    });
+    matrix.write_png(&cli.output).unwrap_or_else(|e| eprintln!("Write PNG occurs error: {}", e));
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Evaluate `expr` for every pixel and channel in `mask`, other channels come from the first input
+    pub fn eval(inputs: &[Matrix<T>], expr: &Expr, mask: ChannelMask) -> Result<Matrix<T>, String> {
+        let first = inputs.first().ok_or("At least one input is required")?;
+        if inputs.iter().any(|m| m.rows != first.rows || m.cols != first.cols) {
+            return Err("The size of all matrix should be same".into());
+        }
+        let mut result = Matrix::<T>::new(first.rows, first.cols);
+        result.data.par_iter_mut().enumerate().for_each(|(index, value)| {
+            let pixels: Vec<[f32; 4]> =
+                inputs.iter().map(|m| m.data[index].map(|x| x.into())).collect();
+            let mut ctx = Context {
+                inputs: &pixels,
+                channel: 0,
+                x: index % first.cols,
+                y: index / first.cols,
+                width: first.cols,
+                height: first.rows,
+            };
+            *value = first.data[index];
+            for (i, channel) in value.iter_mut().enumerate() {
+                if mask[i] {
+                    ctx.channel = i;
+                    let (min, max) = T::bounds(i as u8);
+                    *channel = T::from(expr.eval(&ctx).clamp(min, max));
+                }
+            }
+        });
+        Ok(result)
+    }
+}
@@ -0,0 +1,323 @@
+use std::f32::consts::{E, PI};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Builtin {
+    X,
+    Y,
+    Width,
+    Height,
+    Channel,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+/// Built-in function resolved at parse time
+#[derive(Clone, Copy)]
+pub enum Func {
+    Unary(fn(f32) -> f32),
+    Binary(fn(f32, f32) -> f32),
+    Clamp,
+    /// Variadic, at least one argument
+    Min,
+    Max,
+}
+
+impl Func {
+    /// Number of arguments, `None` for variadic functions
+    fn arity(&self) -> Option<usize> {
+        match self {
+            Func::Unary(_) => Some(1),
+            Func::Binary(_) => Some(2),
+            Func::Clamp => Some(3),
+            Func::Min | Func::Max => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Expr {
+    Number(f32),
+    Builtin(Builtin),
+    /// Input index and an optional fixed channel, the current channel is used otherwise
+    Input(usize, Option<usize>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+/// Per-pixel values an expression is evaluated against
+pub struct Context<'a> {
+    pub inputs: &'a [[f32; 4]],
+    pub channel: usize,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(char),
+}
+
+const FUNCTIONS: [(&str, Func); 22] = [
+    ("abs", Func::Unary(f32::abs)),
+    ("sqrt", Func::Unary(f32::sqrt)),
+    ("exp", Func::Unary(f32::exp)),
+    ("ln", Func::Unary(f32::ln)),
+    ("log2", Func::Unary(f32::log2)),
+    ("log10", Func::Unary(f32::log10)),
+    ("sin", Func::Unary(f32::sin)),
+    ("cos", Func::Unary(f32::cos)),
+    ("tan", Func::Unary(f32::tan)),
+    ("asin", Func::Unary(f32::asin)),
+    ("acos", Func::Unary(f32::acos)),
+    ("atan", Func::Unary(f32::atan)),
+    ("floor", Func::Unary(f32::floor)),
+    ("ceil", Func::Unary(f32::ceil)),
+    ("round", Func::Unary(f32::round)),
+    ("sign", Func::Unary(f32::signum)),
+    ("atan2", Func::Binary(f32::atan2)),
+    ("pow", Func::Binary(f32::powf)),
+    ("hypot", Func::Binary(f32::hypot)),
+    ("clamp", Func::Clamp),
+    ("min", Func::Min),
+    ("max", Func::Max),
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Exponent such as `1e2` or `2.5E-3`
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let sign = usize::from(chars.get(i + 1).is_some_and(|&c| c == '+' || c == '-'));
+                if chars.get(i + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                    i += 1 + sign;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value =
+                text.parse::<f32>().map_err(|e| format!("Invalid number {}: {}", text, e))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/%^(),[]".contains(c) {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        } else {
+            return Err(format!("Unexpected character '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    inputs: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.eat(symbol) {
+            true => Ok(()),
+            false => Err(format!("Expected '{}'", symbol)),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                BinaryOp::Add
+            } else if self.eat('-') {
+                BinaryOp::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                BinaryOp::Mul
+            } else if self.eat('/') {
+                BinaryOp::Div
+            } else if self.eat('%') {
+                BinaryOp::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        match self.eat('^') {
+            true => Ok(Expr::Binary(BinaryOp::Pow, Box::new(base), Box::new(self.unary()?))),
+            false => Ok(base),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.peek().cloned().ok_or("Unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Number(x) => Ok(Expr::Number(x)),
+            Token::Symbol('(') => {
+                let inner = self.sum()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Token::Ident(name) if self.eat('(') => self.call(name),
+            Token::Ident(name) => self.variable(&name),
+            Token::Symbol(c) => Err(format!("Unexpected '{}'", c)),
+        }
+    }
+
+    fn call(&mut self, name: String) -> Result<Expr, String> {
+        let func = FUNCTIONS
+            .iter()
+            .find(|(f, _)| *f == name)
+            .map(|(_, func)| *func)
+            .ok_or(format!("Unknown function '{}'", name))?;
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                args.push(self.sum()?);
+                if self.eat(')') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        if func.arity().map_or(args.is_empty(), |arity| args.len() != arity) {
+            return Err(format!("Wrong number of arguments for '{}'", name));
+        }
+        Ok(Expr::Call(func, args))
+    }
+
+    fn variable(&mut self, name: &str) -> Result<Expr, String> {
+        if let Some(index) = self.inputs.iter().position(|x| x == name) {
+            if !self.eat('[') {
+                return Ok(Expr::Input(index, None));
+            }
+            let channel = match self.peek() {
+                Some(Token::Number(x)) if x.fract() == 0.0 && *x < 4.0 => *x as usize,
+                _ => return Err(format!("Invalid channel index for '{}'", name)),
+            };
+            self.pos += 1;
+            self.expect(']')?;
+            return Ok(Expr::Input(index, Some(channel)));
+        }
+        match name {
+            "x" => Ok(Expr::Builtin(Builtin::X)),
+            "y" => Ok(Expr::Builtin(Builtin::Y)),
+            "w" => Ok(Expr::Builtin(Builtin::Width)),
+            "h" => Ok(Expr::Builtin(Builtin::Height)),
+            "ch" => Ok(Expr::Builtin(Builtin::Channel)),
+            "pi" => Ok(Expr::Number(PI)),
+            "e" => Ok(Expr::Number(E)),
+            _ => Err(format!("Unknown variable '{}'", name)),
+        }
+    }
+}
+
+impl Expr {
+    /// Names reserved by the expression language, inputs cannot use them
+    pub const RESERVED: [&'static str; 7] = ["x", "y", "w", "h", "ch", "pi", "e"];
+
+    pub fn parse(s: &str, inputs: &[String]) -> Result<Self, String> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0, inputs };
+        let expr = parser.sum()?;
+        if parser.pos != parser.tokens.len() {
+            return Err("Unexpected trailing tokens".into());
+        }
+        Ok(expr)
+    }
+
+    pub fn eval(&self, ctx: &Context) -> f32 {
+        match self {
+            Expr::Number(x) => *x,
+            Expr::Builtin(Builtin::X) => ctx.x as f32,
+            Expr::Builtin(Builtin::Y) => ctx.y as f32,
+            Expr::Builtin(Builtin::Width) => ctx.width as f32,
+            Expr::Builtin(Builtin::Height) => ctx.height as f32,
+            Expr::Builtin(Builtin::Channel) => ctx.channel as f32,
+            Expr::Input(index, channel) => ctx.inputs[*index][channel.unwrap_or(ctx.channel)],
+            Expr::Neg(x) => -x.eval(ctx),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(ctx), b.eval(ctx));
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Pow => a.powf(b),
+                }
+            }
+            Expr::Call(func, args) => match func {
+                Func::Unary(f) => f(args[0].eval(ctx)),
+                Func::Binary(f) => f(args[0].eval(ctx), args[1].eval(ctx)),
+                Func::Clamp => args[0].eval(ctx).max(args[1].eval(ctx)).min(args[2].eval(ctx)),
+                Func::Min => args.iter().map(|x| x.eval(ctx)).fold(f32::INFINITY, f32::min),
+                Func::Max => args.iter().map(|x| x.eval(ctx)).fold(f32::NEG_INFINITY, f32::max),
+            },
+        }
+    }
+}
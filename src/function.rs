@@ -1,4 +1,8 @@
 use crate::colormode::*;
+use crate::corners::*;
+use crate::mask::*;
+use crate::matrix::*;
+use crate::morphology::*;
 use crate::neighbors::*;
 use lazy_static::lazy_static;
 use std::collections::HashMap;
@@ -8,6 +12,10 @@ use std::sync::Arc;
 type KernelClosure<T> = Arc<dyn Fn(Neighbors<T>) -> [T; 4] + Sync + Send + 'static>;
 type KernelParamClosure<T> = Arc<dyn Fn(Neighbors<T>, &Vec<f32>) -> [T; 4] + Sync + Send + 'static>;
 
+type GlobalClosure<T> = Arc<dyn Fn(&Matrix<T>) -> Vec<[T; 4]> + Sync + Send + 'static>;
+
+pub type ChannelMask = [bool; 4];
+
 #[derive(Clone)]
 pub enum Function<T>
 where
@@ -15,6 +23,8 @@ where
 {
     Constant(usize, KernelClosure<T>),
     Param(usize, KernelParamClosure<T>, Vec<f32>),
+    Masked(Box<Function<T>>, ChannelMask),
+    Global(usize, GlobalClosure<T>),
 }
 
 lazy_static! {
@@ -66,14 +76,29 @@ lazy_static! {
     };
 }
 
+/// Split a function on `-` and trim the parts, a `-` right after a separator is the sign of the
+/// next number, so `21-path-6-0-6--90` ends with `-90`
+pub fn split_parts(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '-' && i > start {
+            parts.push(s[start..i].trim());
+            start = i + 1;
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
 impl<T> FromStr for Function<T>
 where
-    T: ColorValue,
+    T: ColorValue + 'static,
 {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split('-').map(|x| x.trim()).collect();
+        let parts = split_parts(s);
         if parts.len() < 2 {
             return Err("Invalid function".into());
         }
@@ -85,12 +110,76 @@ where
         match parts[1] {
             "none" => Ok(Function::Constant(size, Arc::new(|n| n.none()))),
             "blur" => Ok(Function::Constant(size, Arc::new(|n| n.blur()))),
+            "geometric" => Ok(Function::Constant(size, Arc::new(|n| n.mean(Mean::Geometric)))),
+            "harmonic" => Ok(Function::Constant(size, Arc::new(|n| n.mean(Mean::Harmonic)))),
+            "contraharmonic" => {
+                let q = parts.get(2).and_then(|x| x.parse::<f32>().ok()).unwrap_or(1.5);
+                Ok(Function::Constant(size, Arc::new(move |n| n.mean(Mean::Contraharmonic(q)))))
+            }
+            "midpoint" => Ok(Function::Constant(size, Arc::new(|n| n.mean(Mean::Midpoint)))),
             "min" => Ok(Function::Constant(size, Arc::new(|n| n.positional(Pos::Min)))),
             "median" => Ok(Function::Constant(size, Arc::new(|n| n.positional(Pos::Mid)))),
             "max" => Ok(Function::Constant(size, Arc::new(|n| n.positional(Pos::Max)))),
+            "amedian" => Ok(Function::Constant(size.max(3), Arc::new(|n| n.adaptive_median()))),
+            "rank" => {
+                let rank = parts.get(2).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.5);
+                if !(0.0..=1.0).contains(&rank) {
+                    return Err("Rank must be in [0, 1]".into());
+                }
+                Ok(Function::Constant(size, Arc::new(move |n| n.positional(Pos::Rank(rank)))))
+            }
+            "trimmed" => {
+                let alpha = parts.get(2).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.25);
+                if !(0.0..0.5).contains(&alpha) {
+                    return Err("Trimmed fraction must be in [0, 0.5)".into());
+                }
+                Ok(Function::Param(size, Arc::new(|n, i| n.trimmed_mean(i[0])), vec![alpha]))
+            }
+            "lee" | "elee" | "frost" | "kuan" => {
+                let first = parts.get(2).and_then(|x| x.parse::<f32>().ok());
+                let damping = parts.get(3).and_then(|x| x.parse::<f32>().ok()).unwrap_or(1.0);
+                let kind = match parts[1] {
+                    "lee" => Speckle::Lee(first.unwrap_or(0.25)),
+                    "elee" => Speckle::EnhancedLee(first.unwrap_or(0.25), damping),
+                    "frost" => Speckle::Frost(first.unwrap_or(2.0)),
+                    _ => Speckle::Kuan(first.unwrap_or(0.25)),
+                };
+                if first.is_some_and(|x| x < 0.0) || damping < 0.0 {
+                    return Err("Speckle parameters should not be negative".into());
+                }
+                Ok(Function::Constant(size, Arc::new(move |n| n.speckle(kind))))
+            }
+            "stat" => {
+                let kind = match parts.get(2).copied() {
+                    Some("variance") => Stat::Variance,
+                    Some("std") => Stat::Deviation,
+                    Some("entropy") => Stat::Entropy,
+                    Some("range") => Stat::Range,
+                    Some("contrast") => Stat::Contrast,
+                    _ => return Err("Unknown local statistic".into()),
+                };
+                match parts.get(3).copied() {
+                    Some("auto") => Ok(Function::statistic_function(size, kind)),
+                    x => {
+                        let gain = x.and_then(|x| x.parse::<f32>().ok()).unwrap_or(1.0);
+                        Ok(Function::Constant(size, Arc::new(move |n| n.statistic(kind, gain))))
+                    }
+                }
+            }
+            "vmedian" => Ok(Function::Constant(size, Arc::new(|n| n.vector_median(false)))),
+            "vdf" => Ok(Function::Constant(size, Arc::new(|n| n.vector_median(true)))),
             "leave0" => Ok(Function::Constant(size, Arc::new(|n| n.leave(0)))),
             "leave1" => Ok(Function::Constant(size, Arc::new(|n| n.leave(1)))),
             "leave2" => Ok(Function::Constant(size, Arc::new(|n| n.leave(2)))),
+            "kuwahara" => Ok(Function::Constant(size, Arc::new(|n| n.kuwahara()))),
+            "akuwahara" => {
+                let q = parts.get(2).and_then(|x| x.parse::<f32>().ok()).unwrap_or(8.0);
+                Ok(Function::Param(
+                    size.max(3),
+                    Arc::new(|n, i| n.anisotropic_kuwahara(i[0])),
+                    vec![q],
+                ))
+            }
             "bilateral" => {
                 if parts.len() < 4 {
                     return Err("Invalid bilateral function format".into());
@@ -107,21 +196,106 @@ where
                 }
                 let l = parts[2].parse::<f32>().unwrap_or(1.0);
                 let theta = parts[3].parse::<f32>().unwrap_or(0.0);
-                Function::motion_blur_function(size, l, theta)
+                let centered = parts.get(4).is_some_and(|&x| x == "c");
+                let bend = parts.get(5).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.0);
+                Function::motion_blur_function(size, l, theta, centered, bend)
                     .map_err(|e| format!("Failed to create motion blur function: {}", e))
             }
+            "path" => {
+                if parts.len() < 4 || !parts.len().is_multiple_of(2) {
+                    return Err("Invalid path function format".into());
+                }
+                let segments: Vec<(f32, f32)> = parts[2..]
+                    .chunks_exact(2)
+                    .map(|x| {
+                        (x[0].parse::<f32>().unwrap_or(1.0), x[1].parse::<f32>().unwrap_or(0.0))
+                    })
+                    .collect();
+                Function::path_blur_function(size, &segments)
+                    .map_err(|e| format!("Failed to create path blur function: {}", e))
+            }
             "gauss" => {
                 if parts.len() < 4 {
                     return Err("Invalid gauss function format".into());
                 }
                 let sigma = parts[3].parse::<f32>().unwrap_or(1.0);
-                match parts[2] {
-                    "blur" => Function::gauss_blur_function(size, sigma)
-                        .map_err(|e| format!("Failed to create gauss blur function: {}", e)),
-                    "sharpen" => Function::gauss_sharpen_function(size, sigma)
-                        .map_err(|e| format!("Failed to create gauss sharpen function: {}", e)),
-                    _ => Err("Unknown gauss function type".into()),
+                let theta = parts.get(4).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.0);
+                let steered = (theta.to_radians().cos(), -theta.to_radians().sin());
+                let (x, y) = ((1.0, 0.0), (0.0, 1.0));
+                let derivative = match parts[2] {
+                    "blur" => {
+                        return Function::gauss_blur_function(size, sigma)
+                            .map_err(|e| format!("Failed to create gauss blur function: {}", e));
+                    }
+                    "sharpen" => {
+                        return Function::gauss_sharpen_function(size, sigma).map_err(|e| {
+                            format!("Failed to create gauss sharpen function: {}", e)
+                        });
+                    }
+                    "log" => {
+                        return Function::log_function(size, sigma)
+                            .map_err(|e| format!("Failed to create LoG function: {}", e));
+                    }
+                    "dx" => vec![x],
+                    "dy" => vec![y],
+                    "dxx" => vec![x, x],
+                    "dyy" => vec![y, y],
+                    "dxy" => vec![x, y],
+                    "d" => vec![steered],
+                    "dd" => vec![steered, steered],
+                    _ => return Err("Unknown gauss function type".into()),
+                };
+                Function::gauss_derivative_function(size, sigma, &derivative)
+                    .map_err(|e| format!("Failed to create gauss derivative function: {}", e))
+            }
+            "gabor" | "gaborbank" => {
+                if parts.len() < 4 {
+                    return Err("Invalid gabor function format".into());
                 }
+                let number = |i: usize, default: f32| {
+                    parts.get(i).and_then(|x| x.parse::<f32>().ok()).unwrap_or(default)
+                };
+                let lambda = number(2, 4.0);
+                let (sigma, gamma, psi) =
+                    (number(4, 0.56 * lambda), number(5, 0.5), number(6, 0.0));
+                if lambda <= 0.0 || sigma <= 0.0 || gamma <= 0.0 {
+                    return Err("Wavelength, sigma and aspect ratio must be positive".into());
+                }
+                match parts[1] {
+                    "gabor" => Ok(Function::Param(
+                        size,
+                        Arc::new(|n, i| n.kernel(i)),
+                        Function::<T>::gabor_kernel(
+                            size,
+                            lambda,
+                            number(3, 0.0),
+                            sigma,
+                            gamma,
+                            psi,
+                        ),
+                    )),
+                    _ => {
+                        let count = parts[3].parse::<usize>().unwrap_or(8).max(1);
+                        let hue = parts.last().is_some_and(|&x| x == "hue");
+                        Ok(Function::gabor_bank_function(
+                            size, count, lambda, sigma, gamma, psi, hue,
+                        ))
+                    }
+                }
+            }
+            "usm" => {
+                if parts.len() < 5 {
+                    return Err("Invalid usm function format".into());
+                }
+                let sigma = parts[2].parse::<f32>().unwrap_or(1.0);
+                let amount = parts[3].parse::<f32>().unwrap_or(1.0);
+                let threshold = parts[4].parse::<f32>().unwrap_or(0.0);
+                let lightness = parts.get(5).is_some_and(|&x| x == "l");
+                if amount < 0.0 || threshold < 0.0 {
+                    return Err("Amount and threshold should not be negative".into());
+                }
+                Function::usm_function(size, sigma, amount, threshold, lightness)
+                    .map_err(|e| format!("Failed to create usm function: {}", e))
             }
             "dog" => {
                 if parts.len() < 5 {
@@ -141,6 +315,128 @@ where
                 Function::emboss_function(size, direction)
                     .map_err(|e| format!("Failed to creat emboss function: {}", e))
             }
+            "depth" => {
+                if parts.len() < 5 {
+                    return Err("Invalid depth function format".into());
+                }
+                let max = parts[3].parse::<f32>().unwrap_or(1.0);
+                let map = Mask::read_png(&parts[4..].join("-"))
+                    .map_err(|e| format!("Failed to read depth map: {}", e))?;
+                Function::depth_blur_function(size, parts[2], max, map)
+                    .map_err(|e| format!("Failed to create depth blur function: {}", e))
+            }
+            "lens" => {
+                if parts.len() < 3 {
+                    return Err("Invalid lens function format".into());
+                }
+                let sides = match parts[2] {
+                    "disk" => 0,
+                    x => x.parse::<usize>().map_err(|e| format!("Invalid aperture: {}", e))?,
+                };
+                let rotation = parts.get(3).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.0);
+                let boost = parts.get(4).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.0);
+                Function::lens_blur_function(size, sides, rotation, boost)
+                    .map_err(|e| format!("Failed to create lens blur function: {}", e))
+            }
+            "zoom" | "spin" => {
+                if parts.len() < 3 {
+                    return Err(format!("Invalid {} function format", parts[1]));
+                }
+                let amount = parts[2].parse::<f32>().unwrap_or(0.1);
+                let cx = parts.get(3).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.5);
+                let cy = parts.get(4).and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.5);
+                let param = vec![amount, cx, cy];
+                match parts[1] {
+                    "zoom" => {
+                        Ok(Function::Param(size, Arc::new(|n, i| n.zoom(i[0], i[1], i[2])), param))
+                    }
+                    _ => {
+                        Ok(Function::Param(size, Arc::new(|n, i| n.spin(i[0], i[1], i[2])), param))
+                    }
+                }
+            }
+            "guided" => {
+                if parts.len() < 3 {
+                    return Err("Invalid guided function format".into());
+                }
+                let eps = parts[2].parse::<f32>().unwrap_or(0.01);
+                if eps <= 0.0 {
+                    return Err("Epsilon must be a positive value".into());
+                }
+                let guide = match parts.len() {
+                    3 => None,
+                    _ => Some(
+                        Mask::read_png(&parts[3..].join("-"))
+                            .map_err(|e| format!("Failed to read guide image: {}", e))?,
+                    ),
+                };
+                Ok(Function::Global(
+                    size,
+                    Arc::new(move |m| m.guided_filter(size / 2, eps, guide.as_ref())),
+                ))
+            }
+            "nlm" => {
+                if parts.len() < 4 {
+                    return Err("Invalid nlm function format".into());
+                }
+                let h = parts[2].parse::<f32>().unwrap_or(0.1);
+                let patch = parts[3].parse::<usize>().unwrap_or(3);
+                let fast = parts.get(4).is_some_and(|&x| x == "fast");
+                if h <= 0.0 || patch % 2 != 1 {
+                    return Err("h must be positive and patch size must be odd".into());
+                }
+                Ok(Function::Global(
+                    size,
+                    Arc::new(move |m| m.non_local_means(size / 2, patch / 2, h, fast)),
+                ))
+            }
+            "diffusion" => {
+                if parts.len() < 4 {
+                    return Err("Invalid diffusion function format".into());
+                }
+                let kappa = parts[2].parse::<f32>().unwrap_or(0.1);
+                let dt = parts[3].parse::<f32>().unwrap_or(0.2);
+                let rational = match parts.get(4) {
+                    None | Some(&"exp") => false,
+                    Some(&"rational") => true,
+                    _ => return Err("Unknown diffusion conductance".into()),
+                };
+                if kappa <= 0.0 || dt <= 0.0 || dt > 0.25 {
+                    return Err("kappa must be positive and time step must be in (0, 0.25]".into());
+                }
+                Ok(Function::Param(
+                    size.max(3),
+                    Arc::new(move |n, i| n.diffusion(i[0], i[1], rational)),
+                    vec![kappa, dt],
+                ))
+            }
+            "morph" => {
+                if parts.len() < 4 {
+                    return Err("Invalid morph function format".into());
+                }
+                let op = Morph::from_str(parts[2])?;
+                let element = Element::new(size, parts[3], parts.get(4).copied())?;
+                Ok(Function::morphology_function(op, element))
+            }
+            "corners" => {
+                let corners = Corners::from_str(s)?;
+                Ok(Function::Global(size, Arc::new(move |m| m.corners(&corners))))
+            }
+            "canny" => {
+                if parts.len() < 4 {
+                    return Err("Invalid canny function format".into());
+                }
+                let low = parts[2].parse::<f32>().unwrap_or(0.05);
+                let high = parts[3].parse::<f32>().unwrap_or(0.15);
+                let sigma =
+                    parts.get(4).and_then(|x| x.parse::<f32>().ok()).unwrap_or(size as f32 / 3.0);
+                if sigma <= 0.0 || low <= 0.0 || low > high {
+                    return Err(
+                        "Sigma must be positive and thresholds must satisfy 0 < low <= high".into(),
+                    );
+                }
+                Ok(Function::Global(size, Arc::new(move |m| m.canny(sigma, low, high))))
+            }
             kernel_name => {
                 let kernel = STATIC_KERNELS.get(kernel_name).ok_or("Unknown function type")?;
                 if kernel.len() != size * size {
@@ -154,13 +450,49 @@ where
 
 impl<T> Function<T>
 where
-    T: ColorValue,
+    T: ColorValue + 'static,
 {
     #[inline]
     pub fn calculate(&self, input: Neighbors<T>) -> [T; 4] {
         match self {
             Self::Constant(_, f) => f(input),
             Self::Param(_, f, x) => f(input, x),
+            Self::Masked(f, mask) => {
+                let center = input.none();
+                let mut result = f.calculate(input);
+                for i in 0..4 {
+                    if !mask[i] {
+                        result[i] = center[i];
+                    }
+                }
+                result
+            }
+            Self::Global(_, _) => unreachable!("Global functions are applied to the whole matrix"),
+        }
+    }
+
+    #[inline]
+    pub fn is_global(&self) -> bool {
+        match self {
+            Self::Global(_, _) => true,
+            Self::Masked(f, _) => f.is_global(),
+            _ => false,
+        }
+    }
+
+    /// Apply a whole-image function, `None` for functions evaluated window by window
+    pub fn global(&self, matrix: &Matrix<T>) -> Option<Vec<[T; 4]>> {
+        match self {
+            Self::Global(_, f) => Some(f(matrix)),
+            Self::Masked(f, mask) => f.global(matrix).map(|mut result| {
+                for (value, source) in result.iter_mut().zip(matrix.data.iter()) {
+                    for i in (0..4).filter(|&i| !mask[i]) {
+                        value[i] = source[i];
+                    }
+                }
+                result
+            }),
+            _ => None,
         }
     }
 
@@ -169,6 +501,8 @@ where
         match self {
             Self::Constant(x, _) => *x,
             Self::Param(x, _, _) => *x,
+            Self::Masked(f, _) => f.size(),
+            Self::Global(x, _) => *x,
         }
     }
 
@@ -176,10 +510,47 @@ where
     pub fn param(self) -> Option<Vec<f32>> {
         match self {
             Self::Param(_, _, param) => Some(param),
+            Self::Masked(f, _) => f.param(),
             _ => None,
         }
     }
 
+    /// Channels the function writes, the rest pass through unchanged
+    pub fn channels(&self) -> ChannelMask {
+        match self {
+            Self::Masked(_, mask) => *mask,
+            _ => [true; 4],
+        }
+    }
+
+    /// Restrict the function to the channels set in `mask`, other channels pass through unchanged.
+    pub fn masked(self, mask: ChannelMask) -> Self {
+        match self {
+            Self::Masked(f, _) => f.masked(mask),
+            f if mask == [true; 4] => f,
+            f => Self::Masked(Box::new(f), mask),
+        }
+    }
+
+    /// Parse a channel mask such as `uv` (luva), `l` or `012`, channel names follow the color mode.
+    pub fn parse_channels(s: &str) -> Result<ChannelMask, String> {
+        let mut mask = [false; 4];
+        for c in s.trim().to_lowercase().chars() {
+            let index = match c.to_digit(10) {
+                Some(x) if x < 4 => x as usize,
+                _ => T::CHANNEL_NAMES
+                    .iter()
+                    .position(|&name| name == c)
+                    .ok_or(format!("Unknown channel '{}'", c))?,
+            };
+            mask[index] = true;
+        }
+        if mask == [false; 4] {
+            return Err("Channel mask should not be empty".into());
+        }
+        Ok(mask)
+    }
+
     fn gauss_blur_function(size: usize, sigma: f32) -> Result<Self, String> {
         if sigma <= 0.0 {
             return Err("Sigma must be a positive value".into());
@@ -235,69 +606,209 @@ where
         Ok(Self::Param(size, Arc::new(|n, i| n.kernel(&i)), kernel))
     }
 
-    fn motion_blur_function(size: usize, l: f32, theta: f32) -> Result<Self, String> {
-        if l <= 0.0 || size < l as usize {
-            return Err("l should be positive and less than or equal to the kernel size".into());
-        }
-        let mut kernel = vec![0.0; size * size];
+    /// Scale-normalized Gaussian derivative along the unit `directions` (`x` right, `y` down),
+    /// first order for one direction and second order for two
+    fn gauss_derivative_function(
+        size: usize,
+        sigma: f32,
+        directions: &[(f32, f32)],
+    ) -> Result<Self, String> {
+        let gauss = Self::gauss_blur_function(size, sigma)?.param().unwrap();
+        let center = (size / 2) as f32;
+        let mut kernel: Vec<f32> = gauss
+            .iter()
+            .enumerate()
+            .map(|(index, g)| {
+                let x = (index % size) as f32 - center;
+                let y = (index / size) as f32 - center;
+                let project = |(u, v): (f32, f32)| (u * x + v * y) / sigma;
+                g * match directions {
+                    [u] => project(*u),
+                    [u, v] => project(*u) * project(*v) - (u.0 * v.0 + u.1 * v.1),
+                    _ => unreachable!(),
+                }
+            })
+            .collect();
+        // The truncated kernel should still give zero on flat areas
+        let mean = kernel.iter().sum::<f32>() / kernel.len() as f32;
+        kernel.iter_mut().for_each(|k| *k -= mean);
+        Ok(Self::Param(size, Arc::new(|n, i| n.kernel(i)), kernel))
+    }
 
-        let center = (size / 2) as i32;
-        let (x0, y0) = (center, center);
+    /// Scale-normalized Laplacian of Gaussian, positive at the center like `laplacian_og`
+    fn log_function(size: usize, sigma: f32) -> Result<Self, String> {
+        let xx = Self::gauss_derivative_function(size, sigma, &[(1.0, 0.0), (1.0, 0.0)])?;
+        let yy = Self::gauss_derivative_function(size, sigma, &[(0.0, 1.0), (0.0, 1.0)])?;
+        let kernel =
+            xx.param().unwrap().iter().zip(yy.param().unwrap()).map(|(a, b)| -a - b).collect();
+        Ok(Self::Param(size, Arc::new(|n, i| n.kernel(i)), kernel))
+    }
 
-        let theta = theta.to_radians();
-        let dx = l * theta.cos();
-        let dy = -l * theta.sin();
-        let x1 = (x0 as f32 + dx).round() as i32;
-        let y1 = (y0 as f32 + dy).round() as i32;
-
-        let points = {
-            let dx = (x1 - x0).abs();
-            let dy = -(y1 - y0).abs();
-            let sx = if x0 < x1 { 1 } else { -1 };
-            let sy = if y0 < y1 { 1 } else { -1 };
-            let mut err = dx + dy;
-            let (mut x, mut y) = (x0, y0);
-            let max_steps = dx.max(-dy);
-            let mut points = Vec::with_capacity(max_steps as usize + 1);
-
-            loop {
-                points.push((x, y));
-                if x == x1 && y == y1 {
-                    break;
-                }
-                let e2 = 2 * err;
-                if e2 >= dy {
-                    err += dy;
-                    x += sx;
-                }
-                if e2 <= dx {
-                    err += dx;
-                    y += sy;
-                }
-            }
-
-            points
-        };
+    /// Gabor kernel with wavelength `lambda`, orientation `theta` in degrees counterclockwise,
+    /// envelope `sigma`, aspect ratio `gamma` and phase `psi` in degrees. The mean is removed and
+    /// the positive lobes sum to one.
+    pub fn gabor_kernel(
+        size: usize,
+        lambda: f32,
+        theta: f32,
+        sigma: f32,
+        gamma: f32,
+        psi: f32,
+    ) -> Vec<f32> {
+        let center = (size / 2) as f32;
+        let (sin, cos) = theta.to_radians().sin_cos();
+        let mut kernel: Vec<f32> = (0..size * size)
+            .map(|index| {
+                let x = (index % size) as f32 - center;
+                let y = center - (index / size) as f32;
+                let (u, v) = (x * cos + y * sin, -x * sin + y * cos);
+                let envelope = (-(u * u + gamma * gamma * v * v) / (2.0 * sigma * sigma)).exp();
+                envelope * (2.0 * std::f32::consts::PI * u / lambda + psi.to_radians()).cos()
+            })
+            .collect();
+        let mean = kernel.iter().sum::<f32>() / kernel.len() as f32;
+        kernel.iter_mut().for_each(|k| *k -= mean);
+        let positive: f32 = kernel.iter().filter(|&&k| k > 0.0).sum();
+        kernel.iter().map(|k| k / positive).collect()
+    }
 
-        let valid_points: Vec<_> = points
-            .into_iter()
-            .filter_map(|(x, y)| {
-                if x >= 0 && x < size as i32 && y >= 0 && y < size as i32 {
-                    Some((x as usize, y as usize))
-                } else {
-                    None
+    /// Bank of `count` Gabor kernels at evenly spaced orientations over half a turn
+    fn gabor_bank_function(
+        size: usize,
+        count: usize,
+        lambda: f32,
+        sigma: f32,
+        gamma: f32,
+        psi: f32,
+        hue: bool,
+    ) -> Self {
+        let kernels = (0..count)
+            .flat_map(|k| {
+                let theta = k as f32 * 180.0 / count as f32;
+                Self::gabor_kernel(size, lambda, theta, sigma, gamma, psi)
+            })
+            .collect();
+        Self::Param(size, Arc::new(move |n, i| n.gabor_bank(i, count, hue)), kernels)
+    }
+
+    /// Unsharp mask, the parameters are the Gaussian kernel followed by amount, threshold and
+    /// the lightness flag
+    fn usm_function(
+        size: usize,
+        sigma: f32,
+        amount: f32,
+        threshold: f32,
+        lightness: bool,
+    ) -> Result<Self, String> {
+        let mut param = Self::gauss_blur_function(size, sigma)?.param().unwrap();
+        param.extend([amount, threshold, lightness as u8 as f32]);
+        let area = size * size;
+        Ok(Self::Param(
+            size,
+            Arc::new(move |n, i| n.unsharp(&i[..area], i[area], i[area + 1], i[area + 2] > 0.0)),
+            param,
+        ))
+    }
+
+    /// Motion along an arc of length `l` starting at heading `theta` and turning by `bend` degrees
+    fn motion_blur_function(
+        size: usize,
+        l: f32,
+        theta: f32,
+        centered: bool,
+        bend: f32,
+    ) -> Result<Self, String> {
+        if l <= 0.0 || l > size as f32 {
+            return Err("l should be positive and less than or equal to the kernel size".into());
+        }
+        let theta = theta.to_radians();
+        let curvature = bend.to_radians() / l;
+        let start = if centered { -l / 2.0 } else { 0.0 };
+        let steps = (l.ceil() as usize * 4).max(1);
+        let points: Vec<(f32, f32)> = (0..=steps)
+            .map(|i| {
+                let s = start + l * i as f32 / steps as f32;
+                match curvature {
+                    0.0 => (s * theta.cos(), -s * theta.sin()),
+                    k => (
+                        ((theta + k * s).sin() - theta.sin()) / k,
+                        ((theta + k * s).cos() - theta.cos()) / k,
+                    ),
                 }
             })
             .collect();
+        Ok(Self::Param(size, Arc::new(|n, i| n.kernel(i)), Self::line_kernel(size, &points)?))
+    }
 
-        let sum = valid_points.len() as f32;
-        let weight = 1.0 / sum;
+    /// Motion along consecutive segments given as (length, angle) pairs, starting at the center
+    fn path_blur_function(size: usize, segments: &[(f32, f32)]) -> Result<Self, String> {
+        let mut points = vec![(0.0, 0.0)];
+        for &(l, theta) in segments {
+            if l <= 0.0 {
+                return Err("Segment length should be positive".into());
+            }
+            let (x, y) = points[points.len() - 1];
+            let theta = theta.to_radians();
+            points.push((x + l * theta.cos(), y - l * theta.sin()));
+        }
+        Ok(Self::Param(size, Arc::new(|n, i| n.kernel(i)), Self::line_kernel(size, &points)?))
+    }
 
-        for (x, y) in valid_points {
-            kernel[y * size + x] = weight;
+    /// Anti-aliased kernel of a polyline relative to the center, weighted evenly by length
+    pub fn line_kernel(size: usize, points: &[(f32, f32)]) -> Result<Vec<f32>, String> {
+        const STEP: f32 = 0.05;
+        let center = (size / 2) as f32;
+        let mut kernel = vec![0f32; size * size];
+        let mut splat = |x: f32, y: f32, w: f32| {
+            let (x, y) = (x + center, y + center);
+            let (x0, y0) = (x.floor(), y.floor());
+            let (fx, fy) = (x - x0, y - y0);
+            for (dx, dy, weight) in [
+                (0, 0, (1.0 - fx) * (1.0 - fy)),
+                (1, 0, fx * (1.0 - fy)),
+                (0, 1, (1.0 - fx) * fy),
+                (1, 1, fx * fy),
+            ] {
+                let (px, py) = (x0 as isize + dx, y0 as isize + dy);
+                if px >= 0 && py >= 0 && (px as usize) < size && (py as usize) < size {
+                    kernel[py as usize * size + px as usize] += weight * w;
+                }
+            }
+        };
+        match points {
+            [] => return Err("Path should not be empty".into()),
+            [(x, y)] => splat(*x, *y, 1.0),
+            _ => {
+                for segment in points.windows(2) {
+                    let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+                    let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+                    let steps = (length / STEP).ceil().max(1.0) as usize;
+                    for i in 0..steps {
+                        let t = (i as f32 + 0.5) / steps as f32;
+                        splat(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t, length / steps as f32);
+                    }
+                }
+            }
         }
+        let sum: f32 = kernel.iter().sum();
+        if sum <= 0.0 {
+            return Err("Path lies outside the kernel".into());
+        }
+        Ok(kernel.iter().map(|&x| x / sum).collect())
+    }
 
-        Ok(Self::Param(size, Arc::new(|n, i| n.kernel(&i)), kernel))
+    /// Local statistic map stretched so the largest value of each channel is full scale
+    fn statistic_function(size: usize, kind: Stat) -> Self {
+        Function::Global(
+            size,
+            Arc::new(move |m| {
+                let mut result = m.clone();
+                result
+                    .convolve(&Function::Constant(size, Arc::new(move |n| n.statistic(kind, 1.0))));
+                result.stretch();
+                result.data
+            }),
+        )
     }
 
     fn emboss_function(size: usize, direction: String) -> Result<Function<T>, String> {
@@ -333,6 +844,101 @@ where
         Ok(Self::Param(size, Arc::new(|n, i| n.kernel(&i)), kernel))
     }
 
+    /// Blur whose Gaussian sigma or disk radius is `max` scaled by the map value under each pixel
+    fn depth_blur_function(size: usize, shape: &str, max: f32, map: Mask) -> Result<Self, String> {
+        const LEVELS: usize = 32;
+        if max <= 0.0 {
+            return Err("Maximum blur must be a positive value".into());
+        }
+        let area = size * size;
+        let mut kernels = Vec::with_capacity(LEVELS * area);
+        for level in 0..LEVELS {
+            let radius = max * level as f32 / (LEVELS - 1) as f32;
+            let kernel = match (shape, level) {
+                (_, 0) => Self::disk_kernel(size, 0.0),
+                ("gauss", _) => Self::gauss_blur_function(size, radius)?.param().unwrap(),
+                ("disk", _) => Self::disk_kernel(size, radius),
+                _ => return Err("Unknown depth blur shape".into()),
+            };
+            kernels.extend(kernel);
+        }
+        let map = Arc::new(map);
+        Ok(Self::Param(
+            size,
+            Arc::new(move |n, kernels| {
+                let p = n.pos;
+                let value = map.sample(p.row, p.col, p.rows, p.cols);
+                let level = (value * (LEVELS - 1) as f32).round() as usize;
+                n.kernel(&kernels[level * area..(level + 1) * area])
+            }),
+            kernels,
+        ))
+    }
+
+    /// Normalized disk of `radius` with anti-aliased edge, a zero radius keeps only the center
+    pub fn disk_kernel(size: usize, radius: f32) -> Vec<f32> {
+        let center = (size / 2) as f32;
+        let mut kernel = vec![0f32; size * size];
+        for i in 0..size {
+            for j in 0..size {
+                let d = ((i as f32 - center).powi(2) + (j as f32 - center).powi(2)).sqrt();
+                kernel[i * size + j] = (radius + 0.5 - d).clamp(0.0, 1.0);
+            }
+        }
+        let sum: f32 = kernel.iter().sum();
+        kernel.iter().map(|&x| x / sum).collect()
+    }
+
+    fn lens_blur_function(
+        size: usize,
+        sides: usize,
+        rotation: f32,
+        boost: f32,
+    ) -> Result<Self, String> {
+        if sides != 0 && sides < 3 {
+            return Err("Polygon aperture needs at least 3 sides".into());
+        }
+        if boost < 0.0 {
+            return Err("Highlight boost should not be negative".into());
+        }
+        let kernel = Self::aperture_kernel(size, sides, rotation);
+        Ok(Self::Param(size, Arc::new(move |n, i| n.lens(i, boost)), kernel))
+    }
+
+    /// Disk (`sides == 0`) or regular polygon filling the kernel, edges anti-aliased by supersampling
+    pub fn aperture_kernel(size: usize, sides: usize, rotation: f32) -> Vec<f32> {
+        const SAMPLES: usize = 8;
+        let center = (size / 2) as f32;
+        let radius = size as f32 / 2.0;
+        let rotation = rotation.to_radians();
+        let sector = std::f32::consts::TAU / sides.max(1) as f32;
+        let inside = |x: f32, y: f32| {
+            let r = (x * x + y * y).sqrt();
+            if sides == 0 {
+                return r <= radius;
+            }
+            let angle = (y.atan2(x) - rotation).rem_euclid(sector) - sector / 2.0;
+            r * angle.cos() <= radius * (sector / 2.0).cos()
+        };
+
+        let mut kernel = vec![0f32; size * size];
+        for i in 0..size {
+            for j in 0..size {
+                let mut covered = 0;
+                for si in 0..SAMPLES {
+                    for sj in 0..SAMPLES {
+                        let y = i as f32 - center + (si as f32 + 0.5) / SAMPLES as f32 - 0.5;
+                        let x = j as f32 - center + (sj as f32 + 0.5) / SAMPLES as f32 - 0.5;
+                        covered += inside(x, y) as usize;
+                    }
+                }
+                kernel[i * size + j] = covered as f32 / (SAMPLES * SAMPLES) as f32;
+            }
+        }
+        let sum: f32 = kernel.iter().sum();
+        kernel.iter().map(|&x| x / sum).collect()
+    }
+
     pub fn generate_spatial_kernel(size: usize, spatial_sigma: f32) -> Vec<f32> {
         let center_idx = size / 2;
         let mut result: Vec<f32> = vec![0f32; size * size];
@@ -348,3 +954,17 @@ where
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masked_with_every_channel_unwraps() {
+        let function =
+            Function::<Rgba>::from_str("3-none").unwrap().masked([true, false, true, false]);
+        assert!(matches!(function, Function::Masked(_, [true, false, true, false])));
+        let function = function.masked([true; 4]);
+        assert!(!matches!(function, Function::Masked(_, _)));
+    }
+}
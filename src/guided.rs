@@ -0,0 +1,44 @@
+use crate::colormode::*;
+use crate::mask::*;
+use crate::matrix::*;
+use rayon::prelude::*;
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Guided filter (He et al.) on channels normalized to `[0, 1]`. Each channel guides itself
+    /// unless a grayscale `guide` is given; all means are box filters of constant cost.
+    pub fn guided_filter(&self, radius: usize, eps: f32, guide: Option<&Mask>) -> Vec<[T; 4]> {
+        let (rows, cols) = (self.rows, self.cols);
+        let mean = |plane: &[f32]| box_mean(plane, rows, cols, radius);
+        let product = |a: &[f32], b: &[f32]| -> Vec<f32> {
+            a.par_iter().zip(b.par_iter()).map(|(x, y)| x * y).collect()
+        };
+        let guide: Option<Vec<f32>> = guide
+            .map(|g| (0..rows * cols).map(|i| g.sample(i / cols, i % cols, rows, cols)).collect());
+
+        let mut result = self.data.clone();
+        for channel in 0..4 {
+            let p = self.plane(channel);
+            let i = guide.as_deref().unwrap_or(&p);
+            let (mean_i, mean_p) = (mean(i), mean(&p));
+            let (corr_ii, corr_ip) = (mean(&product(i, i)), mean(&product(i, &p)));
+
+            let (a, b): (Vec<f32>, Vec<f32>) = (0..rows * cols)
+                .into_par_iter()
+                .map(|k| {
+                    let var_i = corr_ii[k] - mean_i[k] * mean_i[k];
+                    let cov_ip = corr_ip[k] - mean_i[k] * mean_p[k];
+                    let a = cov_ip / (var_i + eps);
+                    (a, mean_p[k] - a * mean_i[k])
+                })
+                .unzip();
+            let (mean_a, mean_b) = (mean(&a), mean(&b));
+            let q: Vec<f32> =
+                (0..rows * cols).into_par_iter().map(|k| mean_a[k] * i[k] + mean_b[k]).collect();
+            Matrix::set_plane(&mut result, channel, &q);
+        }
+        result
+    }
+}
@@ -128,10 +128,10 @@ impl Matrix<Hsla> {
                 let color: palette::Srgba =
                     palette::Hsla::new(x[0].0, x[1].0, x[2].0, x[3].0).into_color();
                 [
-                    Rgba((color.red * 255.0) as u8),
-                    Rgba((color.green * 255.0) as u8),
-                    Rgba((color.blue * 255.0) as u8),
-                    Rgba((color.alpha * 255.0) as u8),
+                    Rgba((color.red * 255.0).round() as u8),
+                    Rgba((color.green * 255.0).round() as u8),
+                    Rgba((color.blue * 255.0).round() as u8),
+                    Rgba((color.alpha * 255.0).round() as u8),
                 ]
             })
             .collect();
@@ -169,10 +169,10 @@ impl Matrix<Luva> {
                 let color: palette::Srgba =
                     palette::Luva::new(x[0].0, x[1].0, x[2].0, x[3].0).into_color();
                 [
-                    Rgba((color.red * 255.0) as u8),
-                    Rgba((color.green * 255.0) as u8),
-                    Rgba((color.blue * 255.0) as u8),
-                    Rgba((color.alpha * 255.0) as u8),
+                    Rgba((color.red * 255.0).round() as u8),
+                    Rgba((color.green * 255.0).round() as u8),
+                    Rgba((color.blue * 255.0).round() as u8),
+                    Rgba((color.alpha * 255.0).round() as u8),
                 ]
             })
             .collect();
@@ -7,21 +7,34 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 mod add;
+mod alpha;
+mod canny;
 mod colormode;
 mod convolve;
+mod corners;
+mod eval;
+mod expr;
 mod function;
+mod guided;
 mod io;
+mod mask;
 mod matrix;
+mod morphology;
 mod neighbors;
+mod nlm;
+mod region;
 
 use crate::add::*;
 use crate::colormode::*;
 use crate::convolve::*;
+use crate::eval::*;
 use std::env::args_os;
 
 fn main() {
     match args_os().nth(1) {
         Some(x) if x == "add" => add_cli(),
+        Some(x) if x == "blend" => blend_cli(),
+        Some(x) if x == "eval" => eval_cli(),
         Some(x) if x == "rgba" => convolve_cli::<Rgba>(),
         Some(x) if x == "hsla" => convolve_cli::<Hsla>(),
         Some(x) if x == "luva" => convolve_cli::<Luva>(),
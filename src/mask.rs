@@ -0,0 +1,107 @@
+use crate::colormode::*;
+use crate::matrix::*;
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+
+/// Grayscale weights in `[0, 1]`, read from any 8-bit PNG
+pub struct Mask {
+    pub rows: usize,
+    pub cols: usize,
+    pub data: Vec<f32>,
+}
+
+impl Mask {
+    pub fn read_png(filename: &str) -> io::Result<Self> {
+        let file = fs::File::open(filename)?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| io::Error::other(format!("Failed to read PNG info: {}", e)))?;
+        let info = reader.info();
+        let width = info.width as usize;
+        let height = info.height as usize;
+
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame_info = reader
+            .next_frame(&mut buffer)
+            .map_err(|e| io::Error::other(format!("Failed to read PNG frame: {}", e)))?;
+        let data_bytes = &buffer[..frame_info.buffer_size()];
+
+        let luma =
+            |x: &[u8]| (0.299 * x[0] as f32 + 0.587 * x[1] as f32 + 0.114 * x[2] as f32) / 255.0;
+        let data = match frame_info.color_type {
+            png::ColorType::Grayscale => data_bytes.iter().map(|&x| x as f32 / 255.0).collect(),
+            png::ColorType::GrayscaleAlpha => {
+                data_bytes.chunks_exact(2).map(|x| x[0] as f32 / 255.0).collect()
+            }
+            png::ColorType::Rgb => data_bytes.chunks_exact(3).map(luma).collect(),
+            png::ColorType::Rgba => data_bytes.chunks_exact(4).map(luma).collect(),
+            png::ColorType::Indexed => unreachable!(),
+        };
+
+        Ok(Mask { rows: height, cols: width, data })
+    }
+
+    /// Nearest value of the mask stretched over an image of `rows` x `cols`
+    #[inline]
+    pub fn sample(&self, row: usize, col: usize, rows: usize, cols: usize) -> f32 {
+        let r = (row * self.rows / rows).min(self.rows - 1);
+        let c = (col * self.cols / cols).min(self.cols - 1);
+        self.data[r * self.cols + c]
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Blend from `original` towards `self` by `strength`, weighted per pixel by `mask`. Hue
+    /// follows the shorter arc of the color circle.
+    pub fn mix(
+        &mut self,
+        original: &Matrix<T>,
+        mask: Option<&Mask>,
+        strength: f32,
+    ) -> Result<(), String> {
+        if original.rows != self.rows || original.cols != self.cols {
+            return Err("The size of two matrix should be same".into());
+        }
+        if mask.is_some_and(|m| m.rows != self.rows || m.cols != self.cols) {
+            return Err("The size of mask and matrix should be same".into());
+        }
+        if strength.is_nan() || strength < 0.0 {
+            return Err("Strength should not be negative".into());
+        }
+        self.data.par_iter_mut().enumerate().for_each(|(index, value)| {
+            let weight = mask.map_or(1.0, |m| m.data[index]) * strength;
+            for (i, channel) in value.iter_mut().enumerate() {
+                let x: f32 = original.data[index][i].into();
+                let y: f32 = (*channel).into();
+                *channel = match T::CHANNEL_NAMES[i] {
+                    'h' => T::from(wrap_hue(x + wrap_hue(y - x) * weight)),
+                    _ => T::from(x + (y - x) * weight).clamp(i as u8),
+                };
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hue(h: f32) -> Matrix<Hsla> {
+        Matrix { rows: 1, cols: 1, data: vec![[Hsla(h), Hsla(1.0), Hsla(0.5), Hsla(1.0)]] }
+    }
+
+    #[test]
+    fn mix_follows_the_shorter_hue_arc() {
+        let mut matrix = hue(170.0);
+        matrix.mix(&hue(-170.0), None, 0.5).unwrap();
+        assert!((matrix.data[0][0].0.abs() - 180.0).abs() < 1e-3);
+        assert!(hue(0.0).mix(&hue(0.0), None, -0.5).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use crate::colormode::*;
+use rayon::prelude::*;
 
+#[derive(Clone)]
 pub struct Matrix<T>
 where
     T: ColorValue,
@@ -32,3 +34,166 @@ where
         self.data[index] = value;
     }
 }
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + Widen + 'static,
+{
+    /// Copy into the float color mode that filtering runs in
+    pub fn widen(&self) -> Matrix<T::Float> {
+        let data = self.data.par_iter().map(|pixel| pixel.map(T::widen)).collect();
+        Matrix { rows: self.rows, cols: self.cols, data }
+    }
+
+    /// Round a float matrix back into this color mode
+    pub fn narrow(matrix: &Matrix<T::Float>) -> Self {
+        let data = matrix
+            .data
+            .par_iter()
+            .map(|pixel| [0, 1, 2, 3].map(|i| T::narrow(pixel[i], i as u8)))
+            .collect();
+        Matrix { rows: matrix.rows, cols: matrix.cols, data }
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// One channel normalized to `[0, 1]` by the range of the color mode
+    pub fn plane(&self, channel: usize) -> Vec<f32> {
+        let (min, max) = T::bounds(channel as u8);
+        self.data.par_iter().map(|pixel| (pixel[channel].into() - min) / (max - min)).collect()
+    }
+
+    /// Write a normalized plane back into one channel, clamping to the range of the color mode
+    pub fn set_plane(data: &mut [[T; 4]], channel: usize, plane: &[f32]) {
+        let (min, max) = T::bounds(channel as u8);
+        data.par_iter_mut().zip(plane.par_iter()).for_each(|(pixel, &x)| {
+            pixel[channel] = T::from(min + x.clamp(0.0, 1.0) * (max - min));
+        });
+    }
+
+    /// Stretch each color channel so its largest value reaches the top of the channel range
+    pub fn stretch(&mut self) {
+        for channel in 0..3 {
+            let plane = self.plane(channel);
+            let peak = plane.par_iter().cloned().reduce(|| 0.0, f32::max);
+            if peak > 0.0 {
+                let plane: Vec<f32> = plane.par_iter().map(|x| x / peak).collect();
+                Matrix::set_plane(&mut self.data, channel, &plane);
+            }
+        }
+    }
+
+    /// Relative luminance of every pixel in `[0, 1]`
+    pub fn luminance(&self) -> Vec<f32> {
+        self.data.par_iter().map(|pixel| T::luminance(pixel)).collect()
+    }
+
+    /// Mean absolute difference to `other` with channels normalized to `[0, 1]`
+    pub fn difference(&self, other: &Matrix<T>) -> f32 {
+        let total: f32 = self
+            .data
+            .par_iter()
+            .zip(other.data.par_iter())
+            .map(|(a, b)| {
+                (0..4)
+                    .map(|i| {
+                        let (min, max) = T::bounds(i as u8);
+                        (a[i].into() - b[i].into()).abs() / (max - min)
+                    })
+                    .sum::<f32>()
+            })
+            .sum();
+        total / (self.data.len() * 4) as f32
+    }
+}
+
+/// Mean over the `(2 * radius + 1)` square around each pixel using an integral image,
+/// so the cost does not depend on the radius; windows are cropped at the border
+pub fn box_mean(plane: &[f32], rows: usize, cols: usize, radius: usize) -> Vec<f32> {
+    let stride = cols + 1;
+    let mut integral = vec![0f64; (rows + 1) * stride];
+    for row in 0..rows {
+        let mut line = 0f64;
+        for col in 0..cols {
+            line += plane[row * cols + col] as f64;
+            integral[(row + 1) * stride + col + 1] = integral[row * stride + col + 1] + line;
+        }
+    }
+    let mut result = vec![0f32; rows * cols];
+    result.par_chunks_mut(cols).enumerate().for_each(|(row, line)| {
+        let (top, bottom) = (row.saturating_sub(radius), (row + radius + 1).min(rows));
+        for (col, value) in line.iter_mut().enumerate() {
+            let (left, right) = (col.saturating_sub(radius), (col + radius + 1).min(cols));
+            let sum = integral[bottom * stride + right]
+                - integral[top * stride + right]
+                - integral[bottom * stride + left]
+                + integral[top * stride + left];
+            *value = (sum / ((bottom - top) * (right - left)) as f64) as f32;
+        }
+    });
+    result
+}
+
+/// Separable Gaussian blur of a plane, borders are clamped
+pub fn gauss_plane(plane: &[f32], rows: usize, cols: usize, sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as isize;
+    let weights: Vec<f32> =
+        (-radius..=radius).map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let total: f32 = weights.iter().sum();
+    let weights: Vec<f32> = weights.iter().map(|w| w / total).collect();
+
+    let mut horizontal = vec![0f32; rows * cols];
+    horizontal.par_chunks_mut(cols).enumerate().for_each(|(row, line)| {
+        for (col, value) in line.iter_mut().enumerate() {
+            *value = (-radius..=radius)
+                .zip(weights.iter())
+                .map(|(d, w)| {
+                    let c = (col as isize + d).clamp(0, cols as isize - 1) as usize;
+                    plane[row * cols + c] * w
+                })
+                .sum();
+        }
+    });
+    let mut result = vec![0f32; rows * cols];
+    result.par_chunks_mut(cols).enumerate().for_each(|(row, line)| {
+        for (col, value) in line.iter_mut().enumerate() {
+            *value = (-radius..=radius)
+                .zip(weights.iter())
+                .map(|(d, w)| {
+                    let r = (row as isize + d).clamp(0, rows as isize - 1) as usize;
+                    horizontal[r * cols + col] * w
+                })
+                .sum();
+        }
+    });
+    result
+}
+
+/// Sobel gradients of a plane scaled so a unit step gives a magnitude of one
+pub fn sobel_plane(plane: &[f32], rows: usize, cols: usize) -> (Vec<f32>, Vec<f32>) {
+    let at = |row: isize, col: isize| {
+        let row = row.clamp(0, rows as isize - 1) as usize;
+        let col = col.clamp(0, cols as isize - 1) as usize;
+        plane[row * cols + col]
+    };
+    (0..rows * cols)
+        .into_par_iter()
+        .map(|index| {
+            let (r, c) = ((index / cols) as isize, (index % cols) as isize);
+            let gx = (at(r - 1, c + 1) + 2.0 * at(r, c + 1) + at(r + 1, c + 1)
+                - at(r - 1, c - 1)
+                - 2.0 * at(r, c - 1)
+                - at(r + 1, c - 1))
+                / 4.0;
+            let gy = (at(r + 1, c - 1) + 2.0 * at(r + 1, c) + at(r + 1, c + 1)
+                - at(r - 1, c - 1)
+                - 2.0 * at(r - 1, c)
+                - at(r - 1, c + 1))
+                / 4.0;
+            (gx, gy)
+        })
+        .unzip()
+}
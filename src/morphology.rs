@@ -0,0 +1,159 @@
+use crate::colormode::*;
+use crate::function::*;
+use crate::matrix::*;
+use crate::neighbors::*;
+use rayon::prelude::*;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Morph {
+    Erode,
+    Dilate,
+    Open,
+    Close,
+    Gradient,
+    TopHat,
+    BlackHat,
+    HitMiss,
+}
+
+impl FromStr for Morph {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "erode" => Ok(Morph::Erode),
+            "dilate" => Ok(Morph::Dilate),
+            "open" => Ok(Morph::Open),
+            "close" => Ok(Morph::Close),
+            "gradient" => Ok(Morph::Gradient),
+            "tophat" => Ok(Morph::TopHat),
+            "blackhat" => Ok(Morph::BlackHat),
+            "hitmiss" => Ok(Morph::HitMiss),
+            _ => Err(format!("Unknown morphology operator {}", s)),
+        }
+    }
+}
+
+/// Structuring element, `hit` is the footprint and `miss` the background of hit-or-miss
+#[derive(Clone)]
+pub struct Element {
+    pub size: usize,
+    pub hit: Vec<bool>,
+    pub miss: Vec<bool>,
+}
+
+impl Element {
+    /// Build `square`, `disk`, `cross`, `diamond`, `line` (with angle `arg`) or a custom
+    /// pattern of `size * size` characters in `1` (hit), `0` (miss) and `x` (ignored)
+    pub fn new(size: usize, shape: &str, arg: Option<&str>) -> Result<Self, String> {
+        let half = (size / 2) as isize;
+        let cells = |f: &dyn Fn(isize, isize) -> bool| -> Vec<bool> {
+            (0..(size * size) as isize)
+                .map(|i| f(i % size as isize - half, i / size as isize - half))
+                .collect()
+        };
+        let hit = match shape {
+            "square" => cells(&|_, _| true),
+            "disk" => cells(&|x, y| x * x + y * y <= half * half),
+            "cross" => cells(&|x, y| x == 0 || y == 0),
+            "diamond" => cells(&|x, y| x.abs() + y.abs() <= half),
+            "line" => {
+                let theta = arg.and_then(|x| x.parse::<f32>().ok()).unwrap_or(0.0).to_radians();
+                let (dx, dy) = (theta.cos(), -theta.sin());
+                cells(&|x, y| (x as f32 * dy - y as f32 * dx).abs() <= 0.5)
+            }
+            pattern if pattern.chars().all(|c| "01x".contains(c)) => {
+                if pattern.len() != size * size {
+                    return Err(format!("Custom element should have {} cells", size * size));
+                }
+                let miss = pattern.chars().map(|c| c == '0').collect();
+                let hit = pattern.chars().map(|c| c == '1').collect();
+                return Element { size, hit, miss }.checked();
+            }
+            _ => return Err(format!("Unknown structuring element {}", shape)),
+        };
+        Element { size, miss: vec![false; size * size], hit }.checked()
+    }
+
+    fn checked(self) -> Result<Self, String> {
+        match self.hit.iter().any(|&x| x) {
+            true => Ok(self),
+            false => Err("Structuring element should not be empty".into()),
+        }
+    }
+
+    /// Point reflection of the element, used by dilation so opening and closing stay dual
+    fn reflected(&self) -> Vec<bool> {
+        self.hit.iter().rev().copied().collect()
+    }
+}
+
+impl<T> Function<T>
+where
+    T: ColorValue + 'static,
+{
+    pub fn morphology_function(op: Morph, element: Element) -> Function<T> {
+        let size = element.size;
+        match op {
+            Morph::Erode => Function::Constant(
+                size,
+                Arc::new(move |n| n.positional_in(Pos::Min, Some(&element.hit))),
+            ),
+            Morph::Dilate => {
+                let footprint = element.reflected();
+                Function::Constant(
+                    size,
+                    Arc::new(move |n| n.positional_in(Pos::Max, Some(&footprint))),
+                )
+            }
+            Morph::HitMiss => Function::Constant(
+                size,
+                Arc::new(move |n| n.hit_or_miss(&element.hit, &element.miss)),
+            ),
+            _ => Function::Global(size, Arc::new(move |m| m.morphology(op, &element))),
+        }
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Compound operators built from erosion and dilation
+    pub fn morphology(&self, op: Morph, element: &Element) -> Vec<[T; 4]> {
+        let erode = Function::morphology_function(Morph::Erode, element.clone());
+        let dilate = Function::morphology_function(Morph::Dilate, element.clone());
+        let apply = |matrix: &Matrix<T>, function: &Function<T>| {
+            let mut result = matrix.clone();
+            result.convolve(function);
+            result
+        };
+        match op {
+            Morph::Open => apply(&apply(self, &erode), &dilate).data,
+            Morph::Close => apply(&apply(self, &dilate), &erode).data,
+            Morph::Gradient => apply(self, &dilate).subtract(&apply(self, &erode)),
+            Morph::TopHat => self.subtract(&apply(&apply(self, &erode), &dilate)),
+            Morph::BlackHat => apply(&apply(self, &dilate), &erode).subtract(self),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Difference of the color channels shifted to start from the minimum of each channel,
+    /// alpha is kept from `self`
+    fn subtract(&self, other: &Matrix<T>) -> Vec<[T; 4]> {
+        self.data
+            .par_iter()
+            .zip(other.data.par_iter())
+            .map(|(a, b)| {
+                let mut pixel = *a;
+                for (i, value) in pixel.iter_mut().enumerate().take(3) {
+                    let (min, _) = T::bounds(i as u8);
+                    *value = T::from(min + (a[i].into() - b[i].into()).max(0.0));
+                }
+                pixel
+            })
+            .collect()
+    }
+}
@@ -1,10 +1,40 @@
 use crate::colormode::*;
 use fast_math::*;
+use palette::IntoColor;
 
 pub enum Pos {
     Max,
     Mid,
     Min,
+    Rank(f32),
+}
+
+#[derive(Clone, Copy)]
+pub enum Mean {
+    Geometric,
+    Harmonic,
+    Contraharmonic(f32),
+    Midpoint,
+}
+
+#[derive(Clone, Copy)]
+pub enum Stat {
+    Variance,
+    Deviation,
+    Entropy,
+    Range,
+    Contrast,
+}
+
+/// Speckle filters. `Lee`, `Kuan` and the first value of `EnhancedLee` are the squared
+/// coefficient of variation of the noise; `Frost` and the second value of `EnhancedLee` are
+/// the damping factor.
+#[derive(Clone, Copy)]
+pub enum Speckle {
+    Lee(f32),
+    EnhancedLee(f32, f32),
+    Frost(f32),
+    Kuan(f32),
 }
 
 pub struct Neighbors<T>
@@ -13,6 +43,16 @@ where
 {
     pub size: usize,
     pub data: Vec<[T; 4]>,
+    pub pos: Position,
+}
+
+/// Location of the window center and the size of the whole image
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
 }
 
 impl<T> Neighbors<T>
@@ -39,39 +79,640 @@ where
         ]
     }
 
+    /// Classical restoration means, computed per channel on values normalized to `[0, 1]` and
+    /// offset by one step so zeros stay finite under logarithms, reciprocals and negative powers
+    pub fn mean(&self, kind: Mean) -> [T; 4] {
+        const OFFSET: f32 = 1.0 / 255.0;
+        let mut result: [T; 4] = [T::from(0u8); 4];
+        result.iter_mut().enumerate().for_each(|(i, value)| {
+            let values = self.data.iter().map(|pixel| Self::normalized(pixel, i) + OFFSET);
+            let area = self.data.len() as f32;
+            let mean = match kind {
+                Mean::Geometric => (values.map(|x| x.ln()).sum::<f32>() / area).exp(),
+                Mean::Harmonic => area / values.map(|x| x.recip()).sum::<f32>(),
+                Mean::Contraharmonic(q) => {
+                    let (upper, lower) =
+                        values.fold((0f32, 0f32), |(u, l), x| (u + x.powf(q + 1.0), l + x.powf(q)));
+                    upper / lower
+                }
+                Mean::Midpoint => {
+                    let (low, high) = values
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(l, h), x| (l.min(x), h.max(x)));
+                    (low + high) / 2.0
+                }
+            };
+            let (min, max) = T::bounds(i as u8);
+            let x = (mean - OFFSET).clamp(0.0, 1.0);
+            *value = T::quantize(min + x * (max - min)).clamp(i as u8);
+        });
+        result
+    }
+
+    /// Strongest response over a bank of `count` Gabor kernels stored back to back. With `hue`
+    /// the bank runs on the luminance and the winning orientation becomes the hue, twice the
+    /// kernel angle so that opposite directions meet.
+    pub fn gabor_bank(&self, kernels: &[f32], count: usize, hue: bool) -> [T; 4] {
+        let area = self.size * self.size;
+        if !hue {
+            let responses = kernels.chunks_exact(area).map(|k| self.kernel(k));
+            return responses
+                .reduce(|mut a, b| {
+                    a.iter_mut().zip(b).take(3).for_each(|(x, y)| *x = if y > *x { y } else { *x });
+                    a
+                })
+                .unwrap();
+        }
+        let luminance: Vec<f32> = self.data.iter().map(|p| T::luminance(p)).collect();
+        let (winner, response) = kernels
+            .chunks_exact(area)
+            .map(|k| k.iter().zip(&luminance).map(|(k, l)| k * l).sum::<f32>())
+            .enumerate()
+            .fold((0, f32::NEG_INFINITY), |a, b| if b.1 > a.1 { b } else { a });
+        let color: palette::Srgb =
+            palette::Hsv::new(winner as f32 * 360.0 / count as f32, 1.0, response.clamp(0.0, 1.0))
+                .into_color();
+        T::from_srgb([color.red, color.green, color.blue, Self::normalized(&self.none(), 3)])
+    }
+
+    /// Unsharp mask against the blur given by `kernel`, differences below `threshold` in
+    /// normalized units are left alone. With `lightness` only the lightness channel is
+    /// sharpened, or the luma difference is added to every channel in RGB.
+    pub fn unsharp(&self, kernel: &[f32], amount: f32, threshold: f32, lightness: bool) -> [T; 4] {
+        let center = self.none();
+        let blurred = self.kernel(kernel);
+        let mut result = center;
+        let sharpen = |value: &mut T, i: usize, difference: f32| {
+            if difference.abs() >= threshold {
+                let (min, max) = T::bounds(i as u8);
+                let x = Self::normalized(&center, i) + amount * difference;
+                *value = T::from(min + x.clamp(0.0, 1.0) * (max - min)).clamp(i as u8);
+            }
+        };
+        let difference = |i: usize| Self::normalized(&center, i) - Self::normalized(&blurred, i);
+        match (lightness, T::CHANNEL_NAMES.iter().position(|&c| c == 'l')) {
+            (true, Some(l)) => sharpen(&mut result[l], l, difference(l)),
+            (true, None) => {
+                let luma = 0.299 * difference(0) + 0.587 * difference(1) + 0.114 * difference(2);
+                for (i, value) in result.iter_mut().enumerate().take(3) {
+                    sharpen(value, i, luma);
+                }
+            }
+            (false, _) => {
+                for (i, value) in result.iter_mut().enumerate().take(3) {
+                    sharpen(value, i, difference(i));
+                }
+            }
+        }
+        result
+    }
+
+    /// Local statistic of each color channel normalized to `[0, 1]`, multiplied by `gain` and
+    /// written over the channel range; alpha is kept from the center pixel
+    pub fn statistic(&self, kind: Stat, gain: f32) -> [T; 4] {
+        let mut result = self.data[self.data.len() / 2];
+        for (i, value) in result.iter_mut().enumerate().take(3) {
+            let values: Vec<f32> = self.data.iter().map(|p| Self::normalized(p, i)).collect();
+            let area = values.len() as f32;
+            let mean = values.iter().sum::<f32>() / area;
+            let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / area;
+            let (low, high) = values
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(l, h), &x| (l.min(x), h.max(x)));
+            // Each statistic is scaled by its largest possible value
+            let statistic = match kind {
+                Stat::Variance => variance * 4.0,
+                Stat::Deviation => variance.sqrt() * 2.0,
+                Stat::Range => high - low,
+                Stat::Contrast if high + low > 0.0 => (high - low) / (high + low),
+                Stat::Contrast => 0.0,
+                Stat::Entropy => {
+                    let mut histogram = [0usize; 256];
+                    for x in &values {
+                        histogram[(x.clamp(0.0, 1.0) * 255.0).round() as usize] += 1;
+                    }
+                    let entropy: f32 = histogram
+                        .iter()
+                        .filter(|&&n| n > 0)
+                        .map(|&n| -(n as f32 / area) * (n as f32 / area).log2())
+                        .sum();
+                    entropy / area.min(256.0).log2().max(1.0)
+                }
+            };
+            let (min, max) = T::bounds(i as u8);
+            *value = T::from(min + (statistic * gain).clamp(0.0, 1.0) * (max - min)).clamp(i as u8);
+        }
+        result
+    }
+
+    /// Adaptive speckle filtering per color channel from the local mean and variance of the
+    /// normalized window, alpha is kept from the center pixel
+    pub fn speckle(&self, kind: Speckle) -> [T; 4] {
+        let half = (self.size / 2) as isize;
+        let center = self.data.len() / 2;
+        let mut result = self.data[center];
+        for (i, value) in result.iter_mut().enumerate().take(3) {
+            let values: Vec<f32> = self.data.iter().map(|p| Self::normalized(p, i)).collect();
+            let area = values.len() as f32;
+            let mean = values.iter().sum::<f32>() / area;
+            let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / area;
+            // Squared coefficient of variation of the window
+            let ci2 = variance / (mean * mean).max(f32::EPSILON);
+            let x = values[center];
+            let filtered = match kind {
+                Speckle::Lee(cu2) => {
+                    let w = (1.0 - cu2 / ci2.max(f32::EPSILON)).max(0.0);
+                    mean + w * (x - mean)
+                }
+                Speckle::Kuan(cu2) => {
+                    let w = ((1.0 - cu2 / ci2.max(f32::EPSILON)) / (1.0 + cu2)).max(0.0);
+                    mean + w * (x - mean)
+                }
+                Speckle::EnhancedLee(cu2, damping) => {
+                    let (cu, cmax, ci) = (cu2.sqrt(), (1.0 + 2.0 * cu2).sqrt(), ci2.sqrt());
+                    match ci {
+                        ci if ci <= cu => mean,
+                        ci if ci < cmax => {
+                            let w = (-damping * (ci - cu) / (cmax - ci)).exp();
+                            mean * w + x * (1.0 - w)
+                        }
+                        _ => x,
+                    }
+                }
+                Speckle::Frost(damping) => {
+                    let (total, weights) = values.iter().enumerate().fold(
+                        (0f32, 0f32),
+                        |(total, weights), (j, value)| {
+                            let (dy, dx) = (
+                                j as isize / self.size as isize - half,
+                                j as isize % self.size as isize - half,
+                            );
+                            let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                            let w = (-damping * ci2 * distance).exp();
+                            (total + value * w, weights + w)
+                        },
+                    );
+                    total / weights
+                }
+            };
+            let (min, max) = T::bounds(i as u8);
+            *value = T::from(min + filtered.clamp(0.0, 1.0) * (max - min)).clamp(i as u8);
+        }
+        result
+    }
+
     #[inline]
     pub fn positional(&self, location: Pos) -> [T; 4] {
+        self.positional_in(location, None)
+    }
+
+    /// Rank filter over the pixels selected by `footprint`, or the whole window
+    pub fn positional_in(&self, location: Pos, footprint: Option<&[bool]>) -> [T; 4] {
         let mut result: [T; 4] = [T::from(0u8); 4];
         result.iter_mut().enumerate().for_each(|(i, value)| {
-            let mut channels: Vec<T> = self.data.iter().map(|pixel| pixel[i]).collect();
+            let mut channels: Vec<T> = self
+                .data
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| footprint.is_none_or(|f| f[*j]))
+                .map(|(_, pixel)| pixel[i])
+                .collect();
             channels.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
 
-            let len = self.data.len();
+            let len = channels.len();
             *value = match location {
                 Pos::Min => channels[0],
                 Pos::Max => channels[len - 1],
                 Pos::Mid => channels[len / 2],
+                Pos::Rank(p) => channels[((len - 1) as f32 * p).round() as usize],
             };
         });
         result
     }
 
+    /// Alpha-trimmed mean, drops the `alpha` fraction at each end of the sorted channel
+    pub fn trimmed_mean(&self, alpha: f32) -> [T; 4] {
+        let mut result: [T; 4] = [T::from(0u8); 4];
+        result.iter_mut().enumerate().for_each(|(i, value)| {
+            let mut channels: Vec<f32> = self.data.iter().map(|pixel| pixel[i].into()).collect();
+            channels.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+            let trim = ((channels.len() as f32 * alpha) as usize).min((channels.len() - 1) / 2);
+            let kept = &channels[trim..channels.len() - trim];
+            *value = T::from(kept.iter().sum::<f32>() / kept.len() as f32);
+        });
+        result
+    }
+
+    /// Adaptive median, grows the window from 3 up to the full size until its median is not an
+    /// impulse, then replaces the center only when the center itself is an impulse
+    pub fn adaptive_median(&self) -> [T; 4] {
+        let center = self.size / 2;
+        let mut result = self.data[center * self.size + center];
+        for (i, value) in result.iter_mut().enumerate() {
+            for half in 1..=center {
+                let mut channels: Vec<T> = (center - half..=center + half)
+                    .flat_map(|row| {
+                        (center - half..=center + half).map(move |col| row * self.size + col)
+                    })
+                    .map(|index| self.data[index][i])
+                    .collect();
+                channels.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+                let (low, high) = (channels[0], channels[channels.len() - 1]);
+                let median = channels[channels.len() / 2];
+                if low < median && median < high {
+                    if !(low < *value && *value < high) {
+                        *value = median;
+                    }
+                    break;
+                }
+                if half == center {
+                    *value = median;
+                }
+            }
+        }
+        result
+    }
+
+    /// Grayscale hit-or-miss: how far the minimum over `hit` rises above the maximum over
+    /// `miss`, per channel and normalized to the range of the color mode
+    pub fn hit_or_miss(&self, hit: &[bool], miss: &[bool]) -> [T; 4] {
+        let low = self.positional_in(Pos::Min, Some(hit));
+        let high = match miss.iter().any(|&x| x) {
+            true => Some(self.positional_in(Pos::Max, Some(miss))),
+            false => None,
+        };
+        let mut result = self.data[self.size * self.size / 2];
+        for (i, value) in result.iter_mut().enumerate().take(3) {
+            let (min, _) = T::bounds(i as u8);
+            let floor = high.map_or(min, |h| h[i].into());
+            *value = T::from(min + (low[i].into() - floor).max(0.0));
+        }
+        result
+    }
+
     #[inline]
     pub fn leave(&self, channel: usize) -> [T; 4] {
-        let mut result = [T::from(0u8), T::from(0u8), T::from(0u8), T::from(255u8)];
-        result[channel] = self.data[self.size * self.size / 2][channel];
+        let center = self.data[self.size * self.size / 2];
+        let mut result = [T::from(0u8), T::from(0u8), T::from(0u8), center[3]];
+        result[channel] = center[channel];
         result
     }
 
     #[inline]
-    pub fn kernel(&self, kernel: &Vec<f32>) -> [T; 4] {
+    pub fn kernel(&self, kernel: &[f32]) -> [T; 4] {
         let (mut sum_0, mut sum_1, mut sum_2) = (0.0f32, 0.0f32, 0.0f32);
+        let (mut sum_transparency, mut weight) = (0.0f32, 0.0f32);
+        let opaque = T::bounds(3).1;
         for (&k, data) in kernel.iter().zip(self.data.iter()) {
             sum_0 += data[0].into() * k;
             sum_1 += data[1].into() * k;
             sum_2 += data[2].into() * k;
+            sum_transparency += (opaque - data[3].into()) * k.abs();
+            weight += k.abs();
+        }
+        // Alpha is the coverage of the kernel footprint, so derivative kernels keep opaque pixels opaque
+        let alpha = match weight {
+            0.0 => self.none()[3].into(),
+            _ => opaque - sum_transparency / weight,
+        };
+        [
+            T::from(sum_0).clamp(0),
+            T::from(sum_1).clamp(1),
+            T::from(sum_2).clamp(2),
+            T::from(alpha).clamp(3),
+        ]
+    }
+
+    /// Weighted mean in linear light, bright pixels gain `boost` extra weight so highlights bloom
+    #[inline]
+    pub fn lens(&self, kernel: &[f32], boost: f32) -> [T; 4] {
+        let opaque = T::bounds(3).1;
+        let (mut sum, mut coverage, mut total) = ([0f32; 3], 0f32, 0f32);
+        for (&k, pixel) in kernel.iter().zip(self.data.iter()) {
+            if k == 0.0 {
+                continue;
+            }
+            let weight = k * (1.0 + boost * T::luminance(pixel).powi(4));
+            // The window is premultiplied, linearize the straight color and premultiply again
+            let a = pixel[3].into() / opaque;
+            if a > 0.0 {
+                for (i, value) in sum.iter_mut().enumerate() {
+                    *value += T::to_linear(i as u8, pixel[i].into() / a) * a * weight;
+                }
+            }
+            coverage += a * weight;
+            total += weight;
         }
-        [T::from(sum_0).clamp(0), T::from(sum_1).clamp(1), T::from(sum_2).clamp(2), T::from(255u8)]
+        let a = coverage / total;
+        let channel = |i: usize| match a {
+            a if a > 0.0 => T::from(T::from_linear(i as u8, sum[i] / total / a) * a).clamp(i as u8),
+            _ => T::from(0u8).clamp(i as u8),
+        };
+        [channel(0), channel(1), channel(2), T::from(a * opaque).clamp(3)]
+    }
+
+    /// Bilinear sample at an offset from the window center, `None` outside the window
+    #[inline]
+    pub fn sample(&self, dx: f32, dy: f32) -> Option<[f32; 4]> {
+        let half = (self.size / 2) as f32;
+        let (x, y) = (dx + half, dy + half);
+        if x < 0.0 || y < 0.0 || x > (self.size - 1) as f32 || y > (self.size - 1) as f32 {
+            return None;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let mut result = [0f32; 4];
+        // Interpolate as `a + (b - a) * t` so equal neighbors are reproduced exactly
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        for (i, value) in result.iter_mut().enumerate() {
+            let top = lerp(
+                self.data[y0 * self.size + x0][i].into(),
+                self.data[y0 * self.size + x1][i].into(),
+                fx,
+            );
+            let bottom = lerp(
+                self.data[y1 * self.size + x0][i].into(),
+                self.data[y1 * self.size + x1][i].into(),
+                fx,
+            );
+            *value = lerp(top, bottom, fy);
+        }
+        Some(result)
+    }
+
+    /// Mean of the samples along a path of offsets, steps leaving the window are skipped
+    #[inline]
+    pub fn path(&self, offsets: impl Iterator<Item = (f32, f32)>) -> [T; 4] {
+        let mut sum = [0f32; 4];
+        let mut count = 0f32;
+        for (dx, dy) in offsets {
+            if let Some(pixel) = self.sample(dx, dy) {
+                sum.iter_mut().zip(pixel).for_each(|(s, p)| *s += p);
+                count += 1.0;
+            }
+        }
+        match count {
+            0.0 => self.none(),
+            _ => [0, 1, 2, 3].map(|i| T::from(sum[i as usize] / count).clamp(i)),
+        }
+    }
+
+    /// Radial blur towards the point (`cx`, `cy`) given as fractions of the image size
+    #[inline]
+    pub fn zoom(&self, strength: f32, cx: f32, cy: f32) -> [T; 4] {
+        let vx = self.pos.col as f32 - cx * self.pos.cols as f32;
+        let vy = self.pos.row as f32 - cy * self.pos.rows as f32;
+        let length = strength * (vx * vx + vy * vy).sqrt();
+        let steps = (length.ceil() as usize).clamp(1, self.size * 2);
+        self.path((0..=steps).map(|t| {
+            let t = t as f32 / steps as f32 * strength;
+            (-vx * t, -vy * t)
+        }))
+    }
+
+    /// Tangential blur along an arc of `angle` degrees around the point (`cx`, `cy`)
+    #[inline]
+    pub fn spin(&self, angle: f32, cx: f32, cy: f32) -> [T; 4] {
+        let vx = self.pos.col as f32 - cx * self.pos.cols as f32;
+        let vy = self.pos.row as f32 - cy * self.pos.rows as f32;
+        let angle = angle.to_radians();
+        let length = angle * (vx * vx + vy * vy).sqrt();
+        let steps = (length.ceil() as usize).clamp(1, self.size * 2);
+        self.path((0..=steps).map(|t| {
+            let phi = (t as f32 / steps as f32 - 0.5) * angle;
+            let (sin, cos) = phi.sin_cos();
+            (vx * cos - vy * sin - vx, vx * sin + vy * cos - vy)
+        }))
+    }
+
+    /// One explicit Perona–Malik step over the 4-neighborhood, gradients normalized to `[0, 1]`
+    #[inline]
+    pub fn diffusion(&self, kappa: f32, dt: f32, rational: bool) -> [T; 4] {
+        let half = self.size / 2;
+        let center = self.none();
+        let neighbors = [
+            self.data[(half - 1) * self.size + half],
+            self.data[(half + 1) * self.size + half],
+            self.data[half * self.size + half - 1],
+            self.data[half * self.size + half + 1],
+        ];
+        let conductance = |g: f32| match rational {
+            true => 1.0 / (1.0 + (g / kappa).powi(2)),
+            false => (-(g / kappa).powi(2)).exp(),
+        };
+        [0, 1, 2, 3].map(|i| {
+            let (min, max) = T::bounds(i);
+            let c = center[i as usize].into();
+            let flux: f32 = neighbors
+                .iter()
+                .map(|n| {
+                    let gradient = (n[i as usize].into() - c) / (max - min);
+                    conductance(gradient.abs()) * gradient
+                })
+                .sum();
+            T::quantize(c + dt * flux * (max - min)).clamp(i)
+        })
+    }
+
+    /// Vector median, the window pixel with the least summed Euclidean distance to the others
+    /// in the current color mode; `directional` ranks by the summed angle between color vectors
+    /// first and breaks ties, such as along the gray axis, by distance
+    pub fn vector_median(&self, directional: bool) -> [T; 4] {
+        let colors: Vec<[f32; 3]> = self.data.iter().map(Self::color_vector).collect();
+        let norm = |a: &[f32; 3]| a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let angle = |a: &[f32; 3], b: &[f32; 3]| match (norm(a), norm(b)) {
+            (x, y) if x > 0.0 && y > 0.0 => {
+                let dot: f32 = a.iter().zip(b).map(|(p, q)| p * q).sum();
+                let cross = [
+                    a[1] * b[2] - a[2] * b[1],
+                    a[2] * b[0] - a[0] * b[2],
+                    a[0] * b[1] - a[1] * b[0],
+                ];
+                norm(&cross).atan2(dot)
+            }
+            (x, y) if x == y => 0.0,
+            _ => std::f32::consts::FRAC_PI_2,
+        };
+        let distance = |a: &[f32; 3], b: &[f32; 3]| {
+            a.iter().zip(b).map(|(p, q)| (p - q).powi(2)).sum::<f32>().sqrt()
+        };
+        let scores: Vec<(f32, f32)> = colors
+            .iter()
+            .map(|a| {
+                let spread = colors.iter().map(|b| distance(a, b)).sum::<f32>();
+                match directional {
+                    true => (colors.iter().map(|b| angle(a, b)).sum::<f32>(), spread),
+                    false => (0.0, spread),
+                }
+            })
+            .collect();
+        let least = scores.iter().map(|s| s.0).fold(f32::INFINITY, f32::min);
+        let (best, _) = scores
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.0 <= least + 1e-4)
+            .min_by(|a, b| a.1.1.partial_cmp(&b.1.1).unwrap())
+            .unwrap();
+        self.data[best]
+    }
+
+    /// Color as a point in space for vector filters. Hue modes use the cylinder
+    /// `(s cos h, s sin h, l)` with saturation and lightness normalized, so hue wraps around;
+    /// other modes keep their channels, which makes Luva distances perceptual.
+    fn color_vector(pixel: &[T; 4]) -> [f32; 3] {
+        match T::CHANNEL_NAMES[0] {
+            'h' => {
+                let (s, l) = (Self::normalized(pixel, 1), Self::normalized(pixel, 2));
+                let (sin, cos) = pixel[0].into().to_radians().sin_cos();
+                [s * cos, s * sin, l]
+            }
+            _ => [pixel[0].into(), pixel[1].into(), pixel[2].into()],
+        }
+    }
+
+    /// Color channel normalized to `[0, 1]`
+    #[inline]
+    fn normalized(pixel: &[T; 4], channel: usize) -> f32 {
+        let (min, max) = T::bounds(channel as u8);
+        (pixel[channel].into() - min) / (max - min)
+    }
+
+    /// Classic Kuwahara, mean of the quadrant with the lowest summed color variance
+    #[inline]
+    pub fn kuwahara(&self) -> [T; 4] {
+        let half = self.size / 2;
+        let mut best = (f32::INFINITY, [0f32; 4]);
+        let (low, high) = (0..half + 1, half..self.size);
+        for (rows, cols) in [
+            (low.clone(), low.clone()),
+            (low.clone(), high.clone()),
+            (high.clone(), low.clone()),
+            (high.clone(), high.clone()),
+        ] {
+            let (mut sum, mut square, mut count) = ([0f32; 4], [0f32; 3], 0f32);
+            for row in rows {
+                for col in cols.clone() {
+                    let pixel = &self.data[row * self.size + col];
+                    for (i, value) in sum.iter_mut().enumerate() {
+                        *value += pixel[i].into();
+                    }
+                    for (i, value) in square.iter_mut().enumerate() {
+                        *value += Self::normalized(pixel, i).powi(2);
+                    }
+                    count += 1.0;
+                }
+            }
+            let mean = sum.map(|x| x / count);
+            let variance: f32 = (0..3)
+                .map(|i| {
+                    let (min, max) = T::bounds(i as u8);
+                    square[i] / count - ((mean[i] - min) / (max - min)).powi(2)
+                })
+                .sum();
+            if variance < best.0 {
+                best = (variance, mean);
+            }
+        }
+        [0, 1, 2, 3].map(|i| T::from(best.1[i as usize]).clamp(i))
+    }
+
+    /// Anisotropic Kuwahara (Kyprianidis et al.), eight smooth sectors of an ellipse shaped and
+    /// oriented by the local structure tensor, weighted by `1 / (1 + σ^q)`
+    #[inline]
+    pub fn anisotropic_kuwahara(&self, q: f32) -> [T; 4] {
+        const SECTORS: usize = 8;
+        let size = self.size;
+        let half = (size / 2) as f32;
+        let sigma = half / 2.0;
+
+        let (mut e, mut f, mut g) = (0f32, 0f32, 0f32);
+        for row in 1..size - 1 {
+            for col in 1..size - 1 {
+                let (dx, dy) = ((col as f32 - half), (row as f32 - half));
+                let weight = (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp();
+                for i in 0..3 {
+                    let gx = (Self::normalized(&self.data[row * size + col + 1], i)
+                        - Self::normalized(&self.data[row * size + col - 1], i))
+                        / 2.0;
+                    let gy = (Self::normalized(&self.data[(row + 1) * size + col], i)
+                        - Self::normalized(&self.data[(row - 1) * size + col], i))
+                        / 2.0;
+                    e += gx * gx * weight;
+                    f += gx * gy * weight;
+                    g += gy * gy * weight;
+                }
+            }
+        }
+        let root = ((e - g).powi(2) + 4.0 * f * f).sqrt();
+        let (lambda1, lambda2) = ((e + g + root) / 2.0, (e + g - root) / 2.0);
+        let anisotropy = match lambda1 + lambda2 {
+            x if x > 0.0 => (lambda1 - lambda2) / x,
+            _ => 0.0,
+        };
+        // Orientation along the edge: the tangent (λ1 - e, -f) is perpendicular to the dominant
+        // gradient, which is (λ1 - g, f) when the first form vanishes
+        let (tx, ty) = match (lambda1 - e).abs() + f.abs() {
+            0.0 => (-f, lambda1 - g),
+            _ => (lambda1 - e, -f),
+        };
+        let (sin, cos) = match tx.abs() + ty.abs() {
+            0.0 => (0.0, 1.0),
+            _ => f32::atan2(ty, tx).sin_cos(),
+        };
+        let a = half * (1.0 + anisotropy);
+        let b = half / (1.0 + anisotropy);
+
+        let mut sum = [[0f32; 4]; SECTORS];
+        let mut square = [0f32; SECTORS];
+        let mut total = [0f32; SECTORS];
+        let sector = std::f32::consts::TAU / SECTORS as f32;
+        for row in 0..size {
+            for col in 0..size {
+                let (dx, dy) = (col as f32 - half, row as f32 - half);
+                let u = (cos * dx + sin * dy) / a;
+                let v = (-sin * dx + cos * dy) / b;
+                let r2 = u * u + v * v;
+                if r2 > 1.0 {
+                    continue;
+                }
+                let radial = (-r2 / (2.0 * 0.33 * 0.33)).exp();
+                let theta = v.atan2(u);
+                let pixel = &self.data[row * size + col];
+                for k in 0..SECTORS {
+                    let delta = (theta - k as f32 * sector + std::f32::consts::PI)
+                        .rem_euclid(std::f32::consts::TAU)
+                        - std::f32::consts::PI;
+                    let weight = match r2 {
+                        0.0 => 1.0 / SECTORS as f32,
+                        _ => radial * (-delta * delta / (2.0 * (sector / 2.0).powi(2))).exp(),
+                    };
+                    for (i, value) in sum[k].iter_mut().enumerate() {
+                        *value += pixel[i].into() * weight;
+                    }
+                    square[k] +=
+                        (0..3).map(|i| Self::normalized(pixel, i).powi(2)).sum::<f32>() * weight;
+                    total[k] += weight;
+                }
+            }
+        }
+
+        let (mut result, mut norm) = ([0f32; 4], 0f32);
+        for k in (0..SECTORS).filter(|&k| total[k] > 0.0) {
+            let mean = sum[k].map(|x| x / total[k]);
+            let mean_square: f32 = (0..3)
+                .map(|i| {
+                    let (min, max) = T::bounds(i as u8);
+                    ((mean[i] - min) / (max - min)).powi(2)
+                })
+                .sum();
+            let deviation = (square[k] / total[k] - mean_square).max(0.0).sqrt();
+            let alpha = 1.0 / (1.0 + (255.0 * deviation).powf(q));
+            for (r, m) in result.iter_mut().zip(mean) {
+                *r += m * alpha;
+            }
+            norm += alpha;
+        }
+        [0, 1, 2, 3].map(|i| T::from(result[i as usize] / norm).clamp(i))
     }
 
     #[inline]
@@ -117,3 +758,60 @@ where
         [T::from(result[0]), T::from(result[1]), T::from(result[2]), T::from(result[3])]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::*;
+    use crate::matrix::*;
+    use std::str::FromStr;
+
+    fn stripes(vertical: bool) -> Matrix<Rgba> {
+        let mut matrix = Matrix::new(24, 24);
+        for row in 0..24 {
+            for col in 0..24 {
+                let x = if vertical { col } else { row };
+                let value = Rgba(if (x / 4) % 2 == 1 { 255 } else { 0 });
+                matrix.set(row, col, [value, value, value, Rgba(255)]);
+            }
+        }
+        matrix
+    }
+
+    #[test]
+    fn lens_averages_straight_colors_in_linear_light() {
+        let pixel = |x: f32| [Rgbaf(x * 0.5), Rgbaf(x * 0.5), Rgbaf(x * 0.5), Rgbaf(127.5)];
+        let pos = Position { row: 0, col: 0, rows: 1, cols: 2 };
+        let n = Neighbors { size: 1, data: vec![pixel(255.0), pixel(0.0)], pos };
+        let result = n.lens(&[1.0, 1.0], 0.0);
+        assert!((result[3].0 - 127.5).abs() < 1e-3);
+        assert!((result[0].0 / 0.5 - 187.5).abs() < 0.5);
+    }
+
+    #[test]
+    fn diffusion_preserves_the_mean() {
+        let mut matrix = Matrix::new(16, 16);
+        for index in 0..256 {
+            let value = Rgba((index * 97 % 256) as u8);
+            matrix.data[index] = [value, value, value, Rgba(255)];
+        }
+        let mean = |m: &Matrix<Rgba>| m.data.iter().map(|p| p[0].0 as f32).sum::<f32>() / 256.0;
+        let before = mean(&matrix);
+        let function = Function::from_str("3-diffusion-0.5-0.2").unwrap();
+        for _ in 0..50 {
+            matrix.convolve(&function);
+        }
+        assert!((mean(&matrix) - before).abs() < 1.0);
+    }
+
+    #[test]
+    fn anisotropic_kuwahara_keeps_axis_aligned_stripes() {
+        let function = Function::from_str("7-akuwahara-8").unwrap();
+        for vertical in [false, true] {
+            let source = stripes(vertical);
+            let mut matrix = source.clone();
+            matrix.convolve(&function);
+            assert!(matrix.difference(&source) < 0.01);
+        }
+    }
+}
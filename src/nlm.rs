@@ -0,0 +1,124 @@
+use crate::colormode::*;
+use crate::matrix::*;
+use rayon::prelude::*;
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Non-local means denoising. Patch distances are the mean squared difference of the color
+    /// channels normalized to `[0, 1]`, and `h` is the filter strength in the same units.
+    pub fn non_local_means(&self, search: usize, patch: usize, h: f32, fast: bool) -> Vec<[T; 4]> {
+        let planes: Vec<Vec<f32>> = (0..4).map(|c| self.plane(c)).collect();
+        let (sums, weights) = match fast {
+            true => self.nlm_integral(&planes, search, patch, h),
+            false => self.nlm_direct(&planes, search, patch, h),
+        };
+        let mut result = self.data.clone();
+        for (channel, sum) in sums.iter().enumerate() {
+            let plane: Vec<f32> =
+                sum.par_iter().zip(weights.par_iter()).map(|(s, w)| s / w).collect();
+            Matrix::set_plane(&mut result, channel, &plane);
+        }
+        result
+    }
+
+    #[inline]
+    fn clamped(&self, row: isize, col: isize) -> usize {
+        let row = row.clamp(0, self.rows as isize - 1) as usize;
+        let col = col.clamp(0, self.cols as isize - 1) as usize;
+        row * self.cols + col
+    }
+
+    fn nlm_direct(
+        &self,
+        planes: &[Vec<f32>],
+        search: usize,
+        patch: usize,
+        h: f32,
+    ) -> ([Vec<f32>; 4], Vec<f32>) {
+        let (search, patch) = (search as isize, patch as isize);
+        let factor = 1.0 / (h * h * ((2 * patch + 1) * (2 * patch + 1) * 3) as f32);
+        let rows: Vec<([Vec<f32>; 4], Vec<f32>)> = (0..self.rows)
+            .into_par_iter()
+            .map(|row| {
+                let mut sums: [Vec<f32>; 4] = Default::default();
+                let mut weights = Vec::with_capacity(self.cols);
+                for col in 0..self.cols {
+                    let (row, col) = (row as isize, col as isize);
+                    let (mut sum, mut total) = ([0f32; 4], 0f32);
+                    for dy in -search..=search {
+                        for dx in -search..=search {
+                            let mut distance = 0f32;
+                            for py in -patch..=patch {
+                                for px in -patch..=patch {
+                                    let a = self.clamped(row + py, col + px);
+                                    let b = self.clamped(row + dy + py, col + dx + px);
+                                    for plane in planes.iter().take(3) {
+                                        distance += (plane[a] - plane[b]).powi(2);
+                                    }
+                                }
+                            }
+                            let weight = (-distance * factor).exp();
+                            let index = self.clamped(row + dy, col + dx);
+                            for (c, value) in sum.iter_mut().enumerate() {
+                                *value += planes[c][index] * weight;
+                            }
+                            total += weight;
+                        }
+                    }
+                    sums.iter_mut().zip(sum).for_each(|(s, x)| s.push(x));
+                    weights.push(total);
+                }
+                (sums, weights)
+            })
+            .collect();
+
+        let mut sums: [Vec<f32>; 4] = Default::default();
+        let mut weights = Vec::with_capacity(self.rows * self.cols);
+        for (row_sums, row_weights) in rows {
+            sums.iter_mut().zip(row_sums).for_each(|(s, x)| s.extend(x));
+            weights.extend(row_weights);
+        }
+        (sums, weights)
+    }
+
+    /// For every offset of the search window, box filter the squared difference image once so
+    /// the patch distance of all pixels comes from an integral image
+    fn nlm_integral(
+        &self,
+        planes: &[Vec<f32>],
+        search: usize,
+        patch: usize,
+        h: f32,
+    ) -> ([Vec<f32>; 4], Vec<f32>) {
+        let (rows, cols) = (self.rows, self.cols);
+        let factor = 1.0 / (h * h * 3.0);
+        let mut sums: [Vec<f32>; 4] = Default::default();
+        sums.iter_mut().for_each(|s| *s = vec![0f32; rows * cols]);
+        let mut weights = vec![0f32; rows * cols];
+        let search = search as isize;
+
+        for dy in -search..=search {
+            for dx in -search..=search {
+                let shifted: Vec<usize> = (0..rows * cols)
+                    .into_par_iter()
+                    .map(|i| self.clamped((i / cols) as isize + dy, (i % cols) as isize + dx))
+                    .collect();
+                let difference: Vec<f32> = (0..rows * cols)
+                    .into_par_iter()
+                    .map(|i| planes.iter().take(3).map(|p| (p[i] - p[shifted[i]]).powi(2)).sum())
+                    .collect();
+                let distance = box_mean(&difference, rows, cols, patch);
+                let weight: Vec<f32> = distance.par_iter().map(|d| (-d * factor).exp()).collect();
+                for (sum, plane) in sums.iter_mut().zip(planes.iter()) {
+                    sum.par_iter_mut().enumerate().for_each(|(i, s)| {
+                        *s += plane[shifted[i]] * weight[i];
+                    });
+                }
+                weights.par_iter_mut().zip(weight.par_iter()).for_each(|(t, w)| *t += w);
+            }
+        }
+        (sums, weights)
+    }
+}
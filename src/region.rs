@@ -0,0 +1,111 @@
+use crate::colormode::*;
+use crate::matrix::*;
+use std::str::FromStr;
+
+#[derive(Clone, Copy)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+impl FromStr for Rect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|x| x.trim().parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|e| format!("Invalid rectangle {}: {}", s, e))?;
+        if parts.len() != 4 {
+            return Err(format!("Rectangle should be x,y,w,h, received {}", s));
+        }
+        if parts[2] == 0 || parts[3] == 0 {
+            return Err(format!("Rectangle should not be empty, received {}", s));
+        }
+        Ok(Rect { x: parts[0], y: parts[1], w: parts[2], h: parts[3] })
+    }
+}
+
+impl Rect {
+    #[inline]
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        col >= self.x && col < self.x + self.w && row >= self.y && row < self.y + self.h
+    }
+
+    /// Grow by `margin` on every side and clip to a `rows` x `cols` image, `None` if nothing is left
+    pub fn expand(&self, margin: usize, rows: usize, cols: usize) -> Option<Rect> {
+        let (x, y) = (self.x.saturating_sub(margin), self.y.saturating_sub(margin));
+        let (right, bottom) =
+            ((self.x + self.w + margin).min(cols), (self.y + self.h + margin).min(rows));
+        (x < right && y < bottom).then(|| Rect { x, y, w: right - x, h: bottom - y })
+    }
+}
+
+/// Union of rectangles, only pixels inside are processed
+#[derive(Clone)]
+pub struct Region(pub Vec<Rect>);
+
+impl Region {
+    #[inline]
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        self.0.iter().any(|rect| rect.contains(row, col))
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: ColorValue + 'static,
+{
+    /// Copy the pixels inside `rect`, which must lie within the matrix
+    pub fn crop(&self, rect: &Rect) -> Matrix<T> {
+        let data = (rect.y..rect.y + rect.h)
+            .flat_map(|row| {
+                let start = row * self.cols + rect.x;
+                self.data[start..start + rect.w].iter().copied()
+            })
+            .collect();
+        Matrix { rows: rect.h, cols: rect.w, data }
+    }
+
+    /// Write the pixels of `rect` back from `crop`, a copy of the area `bounds`
+    pub fn paste(&mut self, crop: &Matrix<T>, bounds: &Rect, rect: &Rect) {
+        for row in rect.y..(rect.y + rect.h).min(bounds.y + bounds.h) {
+            for col in rect.x..(rect.x + rect.w).min(bounds.x + bounds.w) {
+                self.data[row * self.cols + col] =
+                    crop.data[(row - bounds.y) * crop.cols + col - bounds.x];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::*;
+
+    #[test]
+    fn global_functions_only_touch_the_region() {
+        let mut source = Matrix::<Rgbaf>::new(20, 30);
+        for (index, pixel) in source.data.iter_mut().enumerate() {
+            *pixel = [Rgbaf((index * 37 % 256) as f32), Rgbaf(80.0), Rgbaf(160.0), Rgbaf(255.0)];
+        }
+        let function = Function::from_str("5-nlm-0.2-3").unwrap();
+        let region = Region(vec![
+            Rect::from_str("8,5,10,6").unwrap(),
+            Rect::from_str("25,15,10,10").unwrap(),
+        ]);
+        let mut full = source.clone();
+        full.convolve(&function);
+        let mut partial = source.clone();
+        partial.convolve_region(&function, Some(&region));
+        for row in 0..20 {
+            for col in 0..30 {
+                let expected = if region.contains(row, col) { &full } else { &source };
+                assert_eq!(partial.data[row * 30 + col], expected.data[row * 30 + col]);
+            }
+        }
+    }
+}
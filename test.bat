@@ -1,18 +1,50 @@
 cargo rr -- luva input.png output-blur.png -f 3-blur
+cargo rr -- luva input.png output-canny.png -f 5-canny-0.02-0.06-1.4
+cargo rr -- rgba input.png output-contraharmonic.png -f 3-contraharmonic-1.5
+cargo rr -- rgba input.png output-geometric.png -f 3-geometric
+cargo rr -- rgba input.png output-harmonic.png -f 3-harmonic
+cargo rr -- rgba input.png output-midpoint.png -f 3-midpoint
+cargo rr -- rgba input.png output-corners.png -f 9-corners-harris-overlay-1.5-0.01 --points output-corners.csv
+cargo rr -- rgba input.png output-corners-shi.png -f 5-corners-shi-map
+cargo rr -- luva input.png output-depth.png -f 21-depth-gauss-6-mask.png
+cargo rr -- luva input.png output-diffusion.png -f 3-diffusion-0.05-0.2 -t 50 --tolerance 0.0001
 cargo rr -- luva input.png output-dog.png -f 7-dog-3-1-0
 cargo rr -- luva input.png output-sharpen.png -f 3-sharpen
+cargo rr -- rgba input.png output-elee.png -f 7-elee-0.25-1
 cargo rr -- luva input.png output-emboss.png -f 3-emboss-se
+cargo rr -- rgba input.png output-frost.png -f 7-frost-2
+cargo rr -- luva input.png output-gabor.png -f 15-gabor-8-45
+cargo rr -- rgba input.png output-gaborbank.png -f 15-gaborbank-8-8-4.5-0.5-0-hue
 cargo rr -- luva input.png output-gauss-blur.png -f 5-gauss-blur-1
+cargo rr -- luva input.png output-gauss-blur-uv.png -f 5-gauss-blur-2 -c uv
+cargo rr -- rgba input.png output-gauss-blur-preserve.png -f 5-gauss-blur-2 -a preserve
+cargo rr -- luva input.png output-gauss-blur-masked.png -f 9-gauss-blur-3 -m mask.png -s 0.8
+cargo rr -- luva input.png output-gauss-blur-roi.png -f 9-gauss-blur-3 -r 0,0,200,200 -r 300,300,100,50
 cargo rr -- luva input.png output-gauss-sharpen.png -f 5-gauss-sharpen-1
+cargo rr -- luva input.png output-gauss-log.png -f 13-gauss-log-2
+cargo rr -- luva input.png output-gauss-dx.png -f 7-gauss-dx-1
+cargo rr -- luva input.png output-gauss-dd.png -f 9-gauss-dd-1.5-30
+cargo rr -- luva input.png output-guided.png -f 9-guided-0.01
 cargo rr -- luva input.png output-kirsch.png -f 3-kirsch_ne
+cargo rr -- rgba input.png output-kuan.png -f 7-kuan-0.25
+cargo rr -- rgba input.png output-lee.png -f 7-lee-0.25
+cargo rr -- luva input.png output-kuwahara.png -f 7-kuwahara
+cargo rr -- luva input.png output-akuwahara.png -f 9-akuwahara-8
+cargo rr -- rgba input.png output-morph-open.png -f 5-morph-open-disk
+cargo rr -- rgba input.png output-morph-tophat.png -f 9-morph-tophat-line-30
+cargo rr -- rgba input.png output-morph-hitmiss.png -f 3-morph-hitmiss-x0x010x0x
 cargo rr -- luva input.png output-laplacian_4.png -f 3-laplacian_4
 cargo rr -- luva input.png output-laplacian_8.png -f 3-laplacian_8
 cargo rr -- luva input.png output-laplacian_8r.png -f 3-laplacian_8r
 cargo rr -- luva input.png output-laplacian_og.png -f 5-laplacian_og
+cargo rr -- rgba input.png output-lens.png -f 21-lens-6-15-4
 cargo rr -- luva input.png output-max.png -f 3-max
 cargo rr -- luva input.png output-median.png -f 3-median
 cargo rr -- luva input.png output-min.png -f 3-min
 cargo rr -- luva input.png output-motion.png -f 31-motion-31-135
+cargo rr -- luva input.png output-spin.png -f 31-spin-10
+cargo rr -- luva input.png output-motion-curved.png -f 15-motion-7.5-30-c-60
+cargo rr -- luva input.png output-path.png -f 21-path-6-0-6-90-4-200
 cargo rr -- luva input.png output-prewitt-h.png -f 3-prewitt_h
 cargo rr -- luva input.png output-prewitt-v.png -f 3-prewitt_v
 cargo rr -- luva input.png output-robinson.png -f 3-robinson_ne
@@ -21,9 +53,23 @@ cargo rr -- luva input.png output-scharr-v.png -f 3-scharr_v
 cargo rr -- luva input.png output-sobel-h.png -f 3-sobel_h
 cargo rr -- luva input.png output-sobel-v.png -f 3-sobel_v
 cargo rr -- luva input.png output-unsharp_masking.png -f 3-unsharp_masking
+cargo rr -- luva input.png output-zoom.png -f 31-zoom-0.1-0.5-0.5
+cargo rr -- rgba input.png output-amedian.png -f 7-amedian
+cargo rr -- rgba input.png output-rank.png -f 5-rank-0.25
+cargo rr -- rgba input.png output-stat-std.png -f 5-stat-std-auto
+cargo rr -- rgba input.png output-stat-entropy.png -f 7-stat-entropy
+cargo rr -- rgba input.png output-trimmed.png -f 5-trimmed-0.2
+cargo rr -- luva input.png output-usm.png -f 9-usm-2-0.8-0.02-l
+cargo rr -- luva input.png output-vmedian.png -f 5-vmedian
+cargo rr -- rgba input.png output-vdf.png -f 5-vdf
+cargo rr -- luva input.png output-nlm.png -f 21-nlm-0.1-7-fast
 cargo rr -- luva input.png output.png -f 3-none
 cargo rr -- add input.png output-emboss.png output-emboss-m.png
 cargo rr -- add output-laplacian_8r.png output-laplacian_8.png output-laplacian_8m.png
 cargo rr -- add output-prewitt-h.png output-prewitt-v.png output-prewitt-m.png
 cargo rr -- add output-scharr-h.png output-scharr-v.png output-scharr-m.png
 cargo rr -- add output-sobel-h.png output-sobel-v.png output-sobel-m.png
+cargo rr -- add output-sobel-h.png output-sobel-v.png output-sobel-luva-m.png -m --mode luva
+cargo rr -- blend input.png output-emboss.png output-emboss-overlay.png -b overlay -o 0.8
+cargo rr -- eval output-sobel-eval.png "sqrt(a*a+b*b)" output-sobel-h.png output-sobel-v.png
+cargo rr -- eval output-detail.png "(a-b)*4+128" a=input.png b=output-blur.png